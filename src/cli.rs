@@ -1,11 +1,58 @@
 use clap::{Arg, Command};
 use clap_complete::{Shell, generate};
 
+/// `--tag` / `--not-tag` filters shared by every command that targets repositories.
+fn tag_args() -> [Arg; 2] {
+    [
+        Arg::new("tag")
+            .help("Only include repositories with this tag (repeat for AND, comma-separate for OR)")
+            .long("tag")
+            .short('t')
+            .value_name("TAG")
+            .action(clap::ArgAction::Append),
+        Arg::new("not-tag")
+            .help("Exclude repositories with this tag")
+            .long("not-tag")
+            .value_name("TAG")
+            .action(clap::ArgAction::Append),
+    ]
+}
+
+/// `--jobs` for commands that can work on several repositories at once.
+fn jobs_arg() -> Arg {
+    Arg::new("jobs")
+        .help("Number of repositories to work on at the same time")
+        .long("jobs")
+        .short('j')
+        .value_name("N")
+        .default_value("1")
+        .value_parser(clap::value_parser!(u32).range(1..))
+}
+
+/// `--retries` for commands that talk to remotes.
+fn retries_arg() -> Arg {
+    Arg::new("retries")
+        .help("Retry transient network failures this many times (default: defaults.retries, or 2)")
+        .long("retries")
+        .value_name("N")
+        .value_parser(clap::value_parser!(u32))
+}
+
+/// `--stash` for commands that switch branches.
+fn stash_arg() -> Arg {
+    Arg::new("stash")
+        .help("Stash uncommitted changes instead of skipping dirty repositories")
+        .long("stash")
+        .action(clap::ArgAction::SetTrue)
+}
+
 pub fn build_cli() -> Command {
     Command::new("GitPower")
         .version(env!("CARGO_PKG_VERSION"))
         .author("PunGrumpy")
         .about("Manage multiple Git repositories effortlessly")
+        .after_help("Other commands are run as plugins: 'gitpower foo' runs a 'gitpower-foo' executable from PATH.")
+        .allow_external_subcommands(true)
         .arg(
             Arg::new("config")
                 .short('c')
@@ -13,7 +60,19 @@ pub fn build_cli() -> Command {
                 .value_name("FILE")
                 .help("Sets a custom config file"),
         )
-        .subcommand(Command::new("list").about("List all configured repositories"))
+        .arg(
+            Arg::new("git-backend")
+                .long("git-backend")
+                .value_name("BACKEND")
+                .value_parser(["cli", "libgit2"])
+                .global(true)
+                .help("Git implementation for read-only queries (default: cli, or $GITPOWER_GIT_BACKEND)"),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List all configured repositories")
+                .args(tag_args()),
+        )
         .subcommand(
             Command::new("init")
                 .about("Initialize a new repository or add existing repository to config")
@@ -42,6 +101,11 @@ pub fn build_cli() -> Command {
                         .help("Groups to add the repository to (comma-separated)")
                         .long("groups"),
                 )
+                .arg(
+                    Arg::new("tags")
+                        .help("Tags for the repository (comma-separated)")
+                        .long("tags"),
+                )
                 .arg(
                     Arg::new("non-interactive")
                         .help("Run in non-interactive mode")
@@ -57,7 +121,8 @@ pub fn build_cli() -> Command {
                     Arg::new("repos")
                         .help("Specific repositories or groups to check status")
                         .action(clap::ArgAction::Append),
-                ),
+                )
+                .args(tag_args()),
         )
         .subcommand(
             Command::new("sync")
@@ -66,29 +131,503 @@ pub fn build_cli() -> Command {
                     Arg::new("repos")
                         .help("Specific repositories or groups to sync")
                         .action(clap::ArgAction::Append),
+                )
+                .args(tag_args())
+                .arg(retries_arg()),
+        )
+        .subcommand(
+            Command::new("pull")
+                .about("Pull from repositories")
+                .arg(
+                    Arg::new("repos")
+                        .help("Specific repositories or groups to pull")
+                        .action(clap::ArgAction::Append),
+                )
+                .args(tag_args())
+                .arg(retries_arg())
+                .arg(jobs_arg()),
+        )
+        .subcommand(
+            Command::new("clone")
+                .about("Clone configured repositories that are not on disk yet")
+                .arg(
+                    Arg::new("repos")
+                        .help("Specific repositories or groups to clone")
+                        .action(clap::ArgAction::Append),
+                )
+                .args(tag_args())
+                .arg(jobs_arg())
+                .arg(retries_arg()),
+        )
+        .subcommand(
+            Command::new("fetch")
+                .about("Fetch from repositories without merging")
+                .arg(
+                    Arg::new("repos")
+                        .help("Specific repositories or groups to fetch")
+                        .action(clap::ArgAction::Append),
+                )
+                .args(tag_args())
+                .arg(retries_arg())
+                .arg(jobs_arg()),
+        )
+        .subcommand(
+            Command::new("branch")
+                .about("Check out, create, list and prune branches across repositories")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("checkout")
+                        .about("Switch repositories to a branch, tracking a remote branch if needed")
+                        .arg(Arg::new("name").help("Branch to switch to").required(true))
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to switch")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args())
+                        .arg(stash_arg()),
+                )
+                .subcommand(
+                    Command::new("create")
+                        .about("Create a branch and switch to it")
+                        .arg(Arg::new("name").help("Branch to create").required(true))
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to create the branch in")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args())
+                        .arg(
+                            Arg::new("from")
+                                .help("Start the branch at BRANCH instead of the current commit")
+                                .long("from")
+                                .value_name("BRANCH"),
+                        )
+                        .arg(stash_arg()),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("Show which repositories have which branches")
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to list")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args())
+                        .arg(
+                            Arg::new("all")
+                                .help("Include branches that only exist on a remote")
+                                .short('a')
+                                .long("all")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("prune")
+                        .about("Delete local branches that are merged or whose upstream is gone")
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to prune")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args())
+                        .arg(
+                            Arg::new("yes")
+                                .help("Delete without asking for confirmation")
+                                .short('y')
+                                .long("yes")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .help("Also delete unmerged branches whose upstream is gone, losing commits not on any remote")
+                                .long("force")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("commit")
+                .about("Commit in every repository with changes, using the same message")
+                .arg(
+                    Arg::new("message")
+                        .help("Commit message")
+                        .short('m')
+                        .long("message")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("repos")
+                        .help("Specific repositories or groups to commit in")
+                        .action(clap::ArgAction::Append),
+                )
+                .args(tag_args())
+                .arg(
+                    Arg::new("all")
+                        .help("Commit all changes to tracked files, not just staged ones")
+                        .short('a')
+                        .long("all")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("trailer")
+                        .help("Add a 'Key: value' trailer to every commit (repeatable)")
+                        .long("trailer")
+                        .value_name("TRAILER")
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("change-set")
+                        .help("Link the commits with a generated 'Change-Set: <uuid>' trailer")
+                        .long("change-set")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("Tag repositories together for a release")
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
+                .arg(Arg::new("name").help("Tag to create").required(true))
+                .arg(
+                    Arg::new("repos")
+                        .help("Specific repositories or groups to tag")
+                        .action(clap::ArgAction::Append),
+                )
+                .args(tag_args())
+                .arg(
+                    Arg::new("message")
+                        .help("Tag message (default: the tag name)")
+                        .short('m')
+                        .long("message"),
+                )
+                .arg(
+                    Arg::new("sign")
+                        .help("Create GPG-signed tags")
+                        .short('s')
+                        .long("sign")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("ref")
+                        .help("Commit to tag instead of HEAD, e.g. a branch")
+                        .long("ref")
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("push")
+                        .help("Push the tag to each repository's remote")
+                        .long("push")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(retries_arg())
+                .subcommand(
+                    Command::new("delete")
+                        .about("Delete a tag from repositories")
+                        .arg(Arg::new("name").help("Tag to delete").required(true))
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to delete the tag from")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args())
+                        .arg(
+                            Arg::new("remote")
+                                .help("Also delete the tag from each repository's remote")
+                                .long("remote")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(retries_arg()),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("Show which repositories carry which tags")
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to list")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args()),
+                ),
+        )
+        .subcommand(
+            Command::new("snapshot")
+                .about("Record and restore the exact commits of repositories")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("save")
+                        .about("Write each repository's remote, branch and HEAD commit to a lockfile")
+                        .arg(
+                            Arg::new("file")
+                                .help("Lockfile (.yml, .toml or .json)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to record")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args()),
+                )
+                .subcommand(
+                    Command::new("restore")
+                        .about("Check out the commits recorded in a lockfile")
+                        .arg(
+                            Arg::new("file")
+                                .help("Lockfile (.yml, .toml or .json)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to restore")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args())
+                        .arg(
+                            Arg::new("detach")
+                                .help("Detach HEAD at the recorded commits instead of staying on branches")
+                                .long("detach")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(stash_arg()),
+                )
+                .subcommand(
+                    Command::new("verify")
+                        .about("Report where repositories differ from a lockfile")
+                        .arg(
+                            Arg::new("file")
+                                .help("Lockfile (.yml, .toml or .json)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to verify")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args()),
                 ),
         )
         .subcommand(
-            Command::new("pull").about("Pull from repositories").arg(
-                Arg::new("repos")
-                    .help("Specific repositories or groups to pull")
-                    .action(clap::ArgAction::Append),
-            ),
+            Command::new("stash")
+                .about("Stash and restore local changes across repositories")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("save")
+                        .about("Stash local changes, including untracked files, as one batch")
+                        .arg(
+                            Arg::new("message")
+                                .help("Stash message")
+                                .short('m')
+                                .long("message")
+                                .default_value("gitpower stash"),
+                        )
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to stash")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args()),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List stashes, grouped by repository")
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to list")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args()),
+                )
+                .subcommand(
+                    Command::new("pop")
+                        .about("Restore the stashes of a batch made by 'stash save'")
+                        .arg(
+                            Arg::new("repos")
+                                .help("Specific repositories or groups to restore")
+                                .action(clap::ArgAction::Append),
+                        )
+                        .args(tag_args())
+                        .arg(
+                            Arg::new("batch")
+                                .help("Batch to restore (default: the most recent one)")
+                                .long("batch")
+                                .value_name("ID"),
+                        ),
+                ),
         )
         .subcommand(
             Command::new("run")
                 .about("Run a command in all repositories")
-                .arg(Arg::new("command").help("Command to run").required(true))
+                .arg(
+                    Arg::new("command")
+                        .help("Command to run; {name}, {path}, {branch}, {remote}, {groups} and {tag:KEY} are filled in per repository")
+                        .required_unless_present("argv"),
+                )
                 .arg(
                     Arg::new("repos")
                         .help("Specific repositories or groups to run in")
                         .action(clap::ArgAction::Append),
+                )
+                .args(tag_args())
+                .arg(
+                    Arg::new("argv")
+                        .help("Program and arguments to run without a shell; repositories go before `--`")
+                        .value_name("ARGS")
+                        .num_args(1..)
+                        .last(true),
+                )
+                .arg(
+                    Arg::new("shell")
+                        .help("Shell that interprets the command")
+                        .long("shell")
+                        .value_name("SHELL")
+                        .value_parser(["sh", "bash", "zsh", "fish"])
+                        .default_value("sh")
+                        .conflicts_with("argv"),
+                )
+                .arg(jobs_arg())
+                .arg(
+                    Arg::new("output")
+                        .help("Stream lines prefixed with the repository name, or group each repository's output")
+                        .long("output")
+                        .value_name("MODE")
+                        .value_parser(["stream", "group"])
+                        .default_value("stream"),
+                )
+                .arg(
+                    Arg::new("only-if")
+                        .help("Only run where this shell command succeeds, e.g. \"test -f Cargo.toml\"")
+                        .long("only-if")
+                        .value_name("CMD"),
+                )
+                .arg(
+                    Arg::new("fail-fast")
+                        .help("Stop starting repositories after the first failure")
+                        .long("fail-fast")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("max-failures")
+                        .help("Stop starting repositories after N failures")
+                        .long("max-failures")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .conflicts_with("fail-fast"),
+                )
+                .arg(
+                    Arg::new("output-dir")
+                        .help("Write each repository's stdout and stderr, and report.json, to this directory")
+                        .long("output-dir")
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .help("Show the command each repository would run without running it")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("do")
+                .about("Run a command or workflow defined in the config")
+                .arg(
+                    Arg::new("name")
+                        .help("Name of the command or workflow; lists them when omitted"),
+                )
+                .arg(
+                    Arg::new("repos")
+                        .help("Repositories or groups, replacing the configured ones")
+                        .num_args(1..),
+                )
+                .args(tag_args())
+                .arg(jobs_arg())
+                .arg(retries_arg())
+                .arg(
+                    Arg::new("output")
+                        .help("Stream lines prefixed with the repository name, or group each repository's output")
+                        .long("output")
+                        .value_name("MODE")
+                        .value_parser(["stream", "group"])
+                        .default_value("stream"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .help("Show what would run without running it")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("runs")
+                .about("Show the results of past run commands")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List recorded runs, newest first"))
+                .subcommand(
+                    Command::new("show")
+                        .about("Show the results of a recorded run")
+                        .arg(
+                            Arg::new("id")
+                                .help("Run id, or 'latest'")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("logs")
+                                .help("Also print the captured output of every repository")
+                                .long("logs")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
                 ),
         )
         .subcommand(
             Command::new("interactive")
                 .about("Launch interactive mode (like LazyGit)")
-                .alias("i"),
+                .alias("i")
+                .args(tag_args()),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Manage the gitpower config file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("migrate")
+                        .about("Upgrade the config file to the current schema version"),
+                )
+                .subcommand(
+                    Command::new("convert")
+                        .about("Convert the config file to another format")
+                        .arg(
+                            Arg::new("to")
+                                .help("Target format")
+                                .long("to")
+                                .value_parser(["yaml", "toml", "json"])
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .help("Output file (defaults to the config path with the new extension)")
+                                .long("output")
+                                .short('o')
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .help("Overwrite the output file if it exists")
+                                .long("force")
+                                .short('f')
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("schema")
+                        .about("Print the JSON Schema of the config file")
+                        .arg(
+                            Arg::new("output")
+                                .help("Write the schema to a file instead of stdout")
+                                .long("output")
+                                .short('o')
+                                .value_name("FILE"),
+                        ),
+                ),
         )
         .subcommand(
             Command::new("completion")
@@ -0,0 +1,828 @@
+use super::summary::{Outcome, Summary};
+use super::sync::{remote_and_branch, remote_name};
+use crate::config::{Config, Repository, Selection, select_repositories};
+use crate::git::{Commit, GitBackend, GitOutput};
+use crate::process;
+use colored::*;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+/// Switches every selected repository to `branch`. Branches that only exist on
+/// a remote are created locally, tracking the remote one.
+pub fn checkout_branch(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    branch: &str,
+    stash: bool,
+) {
+    let repos = select_repositories(config, selection);
+    if repos.is_empty() {
+        println!("No repositories to check out.");
+        return;
+    }
+
+    println!(
+        "{} {}",
+        "Checking out branch:".green().bold(),
+        branch.yellow()
+    );
+    let summary = for_each_repository(&repos, |_, path| {
+        if git.current_branch(path).as_deref() == Some(branch) {
+            return (Outcome::Ok, format!("already on {}", branch));
+        }
+
+        let remote = if git.branches(path).iter().any(|b| b == branch) {
+            None
+        } else {
+            match find_remote_branch(git, path, branch) {
+                Some(remote) => Some(remote),
+                None => return (Outcome::Skipped, format!("no branch '{}'", branch)),
+            }
+        };
+
+        let stashed = match make_clean(git, path, stash, &format!("checkout {}", branch)) {
+            Ok(stashed) => stashed,
+            Err(result) => return result,
+        };
+
+        let output = match &remote {
+            None => git.checkout(path, branch),
+            Some(remote) => git.create_branch(path, branch, remote, true),
+        };
+        if !output.success {
+            return failure_after_clean(git, path, &stashed, &output);
+        }
+
+        let mut message = format!("switched to {}", branch);
+        if let Some(remote) = remote {
+            message.push_str(&format!(", tracking {}", remote));
+        }
+        let outcome = restore_stashed(git, path, stashed, &mut message);
+        (outcome, message)
+    });
+    summary.print("Checkout complete!");
+}
+
+/// Creates `branch` in every selected repository and checks it out, starting
+/// from `from` (a local or remote branch) or from the current commit.
+pub fn create_branch(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    branch: &str,
+    from: Option<&str>,
+    stash: bool,
+) {
+    let repos = select_repositories(config, selection);
+    if repos.is_empty() {
+        println!("No repositories to create the branch in.");
+        return;
+    }
+
+    println!("{} {}", "Creating branch:".green().bold(), branch.yellow());
+    let summary = for_each_repository(&repos, |_, path| {
+        if git.branches(path).iter().any(|b| b == branch) {
+            return (Outcome::Skipped, format!("'{}' already exists", branch));
+        }
+
+        // Branching off the current commit keeps local changes where they are,
+        // so only switching to another start point needs a clean tree
+        let (start, stashed) = match from {
+            None => ("HEAD".to_string(), None),
+            Some(from) => {
+                let start = if git.branches(path).iter().any(|b| b == from) {
+                    from.to_string()
+                } else {
+                    match find_remote_branch(git, path, from) {
+                        Some(remote) => remote,
+                        None => return (Outcome::Failed, format!("no branch '{}'", from)),
+                    }
+                };
+                match make_clean(git, path, stash, &format!("create {}", branch)) {
+                    Ok(stashed) => (start, stashed),
+                    Err(result) => return result,
+                }
+            }
+        };
+
+        let output = git.create_branch(path, branch, &start, false);
+        if !output.success {
+            return failure_after_clean(git, path, &stashed, &output);
+        }
+
+        let mut message = match from {
+            Some(_) => format!("created {} from {}", branch, start),
+            None => format!("created {}", branch),
+        };
+        let outcome = restore_stashed(git, path, stashed, &mut message);
+        (outcome, message)
+    });
+    summary.print("Branch creation complete!");
+}
+
+/// Prints a matrix of branches (rows) against repositories (columns). With
+/// `all`, branches that only exist on a remote are included.
+pub fn list_branches(config: &Config, selection: &Selection, git: &dyn GitBackend, all: bool) {
+    let repos = select_repositories(config, selection);
+
+    struct Column<'a> {
+        name: &'a str,
+        current: Option<String>,
+        local: Vec<String>,
+        remote: Vec<String>,
+    }
+
+    let mut columns = Vec::new();
+    for repo in repos {
+        let path = shellexpand::tilde(&repo.path);
+        let path = Path::new(path.as_ref());
+        if !path.exists() {
+            eprintln!(
+                "{} {}: Repository path does not exist",
+                "WARNING:".yellow().bold(),
+                repo.name
+            );
+            continue;
+        }
+
+        let remote = if all {
+            git.remote_branches(path)
+                .into_iter()
+                .filter_map(|remote| Some(remote.split_once('/')?.1.to_string()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        columns.push(Column {
+            name: &repo.name,
+            current: git.current_branch(path),
+            local: git.branches(path),
+            remote,
+        });
+    }
+
+    if columns.is_empty() {
+        println!("No repositories to list branches of.");
+        return;
+    }
+
+    let branches: BTreeSet<&str> = columns
+        .iter()
+        .flat_map(|column| column.local.iter().chain(&column.remote))
+        .map(String::as_str)
+        .collect();
+    let names: Vec<&str> = columns.iter().map(|column| column.name).collect();
+    print_matrix(
+        &names,
+        branches.into_iter().map(|branch| {
+            let cells = columns
+                .iter()
+                .map(|column| {
+                    if column.current.as_deref() == Some(branch) {
+                        "●".green()
+                    } else if column.local.iter().any(|b| b == branch) {
+                        "✓".normal()
+                    } else if column.remote.iter().any(|b| b == branch) {
+                        "○".dimmed()
+                    } else {
+                        "·".dimmed()
+                    }
+                })
+                .collect();
+            (branch, cells)
+        }),
+    );
+
+    let mut legend = format!("\n{} checked out  ✓ local", "●".green());
+    if all {
+        legend.push_str(&format!("  {} remote only", "○".dimmed()));
+    }
+    println!("{}", legend);
+}
+
+/// Prints one row of single-character cells per label, with a column under
+/// each repository name in `columns`.
+pub(super) fn print_matrix<'a>(
+    columns: &[&str],
+    rows: impl IntoIterator<Item = (&'a str, Vec<ColoredString>)>,
+) {
+    let rows: Vec<_> = rows.into_iter().collect();
+    let width = rows
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let header: Vec<String> = columns.iter().map(|name| name.bold().to_string()).collect();
+    println!("{:width$}  {}", "", header.join("  "), width = width);
+
+    for (label, cells) in rows {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(columns)
+            .map(|(cell, name)| {
+                format!(
+                    "{}{}",
+                    cell,
+                    " ".repeat(name.chars().count().saturating_sub(1))
+                )
+            })
+            .collect();
+        println!("{:width$}  {}", label, cells.join("  "), width = width);
+    }
+}
+
+/// A local branch `branch prune` offers to delete.
+struct Stale<'a> {
+    repo: &'a Repository,
+    path: PathBuf,
+    branch: String,
+    merged: bool,
+    gone: bool,
+    /// Commits deleting an unmerged branch would lose
+    unpushed: Vec<Commit>,
+}
+
+/// Deletes local branches that are merged into the repository's default branch
+/// or whose upstream is gone, after listing them and asking for confirmation
+/// (skipped with `yes`). The checked-out and protected branches are kept.
+/// Unmerged branches are only deleted with `force`, since their commits may
+/// exist nowhere else.
+pub fn prune_branches(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    yes: bool,
+    force: bool,
+) {
+    let repos = select_repositories(config, selection);
+
+    let mut stale = Vec::new();
+    for repo in repos {
+        let path = shellexpand::tilde(&repo.path);
+        let path = Path::new(path.as_ref());
+        if !path.exists() {
+            eprintln!(
+                "{} {}: Repository path does not exist",
+                "WARNING:".yellow().bold(),
+                repo.name
+            );
+            continue;
+        }
+
+        // Compare with the local default branch, or the remote one when it
+        // was never checked out
+        let (_, default) = remote_and_branch(repo);
+        let local = git.branches(path);
+        let target = if local.iter().any(|b| b == default) {
+            Some(default.to_string())
+        } else {
+            remote_name(git, repo, path)
+                .map(|remote| format!("{}/{}", remote, default))
+                .filter(|remote| git.remote_branches(path).contains(remote))
+        };
+        let merged = match &target {
+            Some(target) => git.merged_branches(path, target),
+            None => {
+                eprintln!(
+                    "{} {}: Default branch '{}' not found, only checking for gone upstreams",
+                    "WARNING:".yellow().bold(),
+                    repo.name,
+                    default
+                );
+                Vec::new()
+            }
+        };
+        let gone = git.gone_branches(path);
+        let current = git.current_branch(path);
+
+        for branch in local {
+            let is_merged = merged.contains(&branch);
+            let is_gone = gone.contains(&branch);
+            if (!is_merged && !is_gone)
+                || current.as_deref() == Some(branch.as_str())
+                || config.is_protected_branch(repo, &branch)
+            {
+                continue;
+            }
+            let unpushed = if is_merged {
+                Vec::new()
+            } else {
+                git.unpushed_commits(path, &branch, target.as_deref())
+            };
+            stale.push(Stale {
+                repo,
+                path: path.to_path_buf(),
+                branch,
+                merged: is_merged,
+                gone: is_gone,
+                unpushed,
+            });
+        }
+    }
+
+    if stale.is_empty() {
+        println!("No branches to prune.");
+        return;
+    }
+
+    let name_width = stale
+        .iter()
+        .map(|s| s.repo.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let branch_width = stale
+        .iter()
+        .map(|s| s.branch.chars().count())
+        .max()
+        .unwrap_or(0);
+    println!(
+        "{}",
+        format!(
+            "  {:name_width$}  {:branch_width$}  REASON",
+            "REPOSITORY",
+            "BRANCH",
+            name_width = name_width.max("REPOSITORY".len()),
+            branch_width = branch_width.max("BRANCH".len())
+        )
+        .bold()
+    );
+    for s in &stale {
+        let reason = match (s.merged, s.gone) {
+            (true, true) => "merged, upstream gone",
+            (true, false) => "merged",
+            _ => "upstream gone",
+        };
+        let lost = match s.unpushed.len() {
+            0 => String::new(),
+            1 => ", 1 commit not on any remote".to_string(),
+            n => format!(", {} commits not on any remote", n),
+        };
+        println!(
+            "  {:name_width$}  {:branch_width$}  {}{}",
+            s.repo.name,
+            s.branch,
+            reason.dimmed(),
+            lost.yellow(),
+            name_width = name_width.max("REPOSITORY".len()),
+            branch_width = branch_width.max("BRANCH".len())
+        );
+        for commit in &s.unpushed {
+            println!(
+                "      {} {}",
+                commit.sha[..commit.sha.len().min(7)].yellow(),
+                commit.summary.dimmed()
+            );
+        }
+    }
+    if !force && stale.iter().any(|s| !s.unpushed.is_empty()) {
+        println!("\nBranches with commits not on any remote are only deleted with --force.");
+    }
+
+    let plural = if stale.len() == 1 { "" } else { "es" };
+    if !yes {
+        if !io::stdin().is_terminal() {
+            println!(
+                "\nNot deleting {} branch{} without confirmation; use --yes.",
+                stale.len(),
+                plural
+            );
+            return;
+        }
+        let confirmed = process::prompt(|| {
+            Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Delete {} branch{}?", stale.len(), plural))
+                .default(false)
+                .interact()
+                .unwrap_or(false)
+        });
+        if !confirmed {
+            println!("Nothing deleted.");
+            return;
+        }
+    }
+
+    println!();
+    let mut summary = Summary::new();
+    for branches in stale.chunk_by(|a, b| a.repo.name == b.repo.name) {
+        let name = &branches[0].repo.name;
+        if process::is_cancelled() {
+            summary.record(name, Outcome::NotRun);
+            continue;
+        }
+
+        let mut outcome = Outcome::Ok;
+        for s in branches {
+            // `git branch -d` checks against HEAD or the upstream, not the
+            // default branch `merged` was found with, so those need `-D` too.
+            // Branches squash-merged upstream look unmerged locally; deleting
+            // those takes `force`, after the listing showed what is lost
+            let output = git.delete_branch(&s.path, &s.branch, s.merged || force);
+            let message = if output.success {
+                format!("deleted {}", s.branch).green()
+            } else {
+                let (failed, mut message) = failure(&output);
+                if !force && !s.merged {
+                    message.push_str(" (use --force to delete)");
+                }
+                outcome = failed;
+                format!("{}: {}", s.branch, message).red()
+            };
+            println!(
+                "  {:name_width$}  {}",
+                name.bold(),
+                message,
+                name_width = name_width
+            );
+        }
+        summary.record(name, outcome);
+    }
+    summary.print("Prune complete!");
+}
+
+/// Runs `work` in every repository, printing one line per repository with
+/// the message it returns, and collects the outcomes.
+pub(super) fn for_each_repository(
+    repos: &[&Repository],
+    work: impl Fn(&Repository, &Path) -> (Outcome, String),
+) -> Summary {
+    let width = repos
+        .iter()
+        .map(|repo| repo.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut summary = Summary::new();
+
+    for repo in repos {
+        if process::is_cancelled() {
+            summary.record(&repo.name, Outcome::NotRun);
+            continue;
+        }
+
+        let path = shellexpand::tilde(&repo.path);
+        let path = Path::new(path.as_ref());
+        let (outcome, message) = if path.exists() {
+            work(repo, path)
+        } else {
+            (
+                Outcome::Failed,
+                "Repository path does not exist".to_string(),
+            )
+        };
+
+        let message = match outcome {
+            Outcome::Ok => message.green(),
+            Outcome::Skipped => message.blue(),
+            _ => message.red(),
+        };
+        println!("  {:width$}  {}", repo.name.bold(), message, width = width);
+        summary.record(&repo.name, outcome);
+    }
+
+    summary
+}
+
+/// Makes sure there are no uncommitted changes to tracked files before
+/// switching branches, stashing them when `stash` is set. Untracked files do
+/// not stop git from switching, so they are left alone unless there is
+/// something else to stash. Returns a note about what was stashed.
+pub(super) fn make_clean(
+    git: &dyn GitBackend,
+    path: &Path,
+    stash: bool,
+    action: &str,
+) -> Result<Option<String>, (Outcome, String)> {
+    let changes = match git.status(path) {
+        Ok(changes) => changes.iter().filter(|c| c.code != "??").count(),
+        Err(e) => return Err((Outcome::Failed, e)),
+    };
+    let plural = if changes == 1 { "" } else { "s" };
+
+    if changes == 0 {
+        Ok(None)
+    } else if !stash {
+        Err((
+            Outcome::Skipped,
+            format!(
+                "{} uncommitted change{}, use --stash to stash them",
+                changes, plural
+            ),
+        ))
+    } else {
+        let output = git.stash(path, &format!("gitpower: before {}", action));
+        if !output.success {
+            return Err(failure(&output));
+        }
+        Ok(Some(format!("stashed {} change{}", changes, plural)))
+    }
+}
+
+/// Pops the changes `make_clean` stashed once the branch switch succeeded,
+/// noting in `message` whether they came back.
+pub(super) fn restore_stashed(
+    git: &dyn GitBackend,
+    path: &Path,
+    stashed: Option<String>,
+    message: &mut String,
+) -> Outcome {
+    let Some(stashed) = stashed else {
+        return Outcome::Ok;
+    };
+    let output = git.stash_pop(path, 0);
+    if output.success {
+        message.push_str(&format!(", {} and restored them", stashed));
+        return Outcome::Ok;
+    }
+    let (outcome, error) = failure(&output);
+    message.push_str(&format!(
+        ", {} but restoring them failed: {}; they are kept in stash@{{0}}",
+        stashed, error
+    ));
+    outcome
+}
+
+/// Reports a failed branch switch after `make_clean`, first popping the
+/// changes it stashed so they are not left behind.
+pub(super) fn failure_after_clean(
+    git: &dyn GitBackend,
+    path: &Path,
+    stashed: &Option<String>,
+    output: &GitOutput,
+) -> (Outcome, String) {
+    let (outcome, mut message) = failure(output);
+    if stashed.is_some() {
+        if git.stash_pop(path, 0).success {
+            message.push_str("; stashed changes restored");
+        } else {
+            message.push_str("; stashed changes left in stash@{0}");
+        }
+    }
+    (outcome, message)
+}
+
+/// `<remote>/<branch>` for the first remote that has `branch`, preferring `origin`.
+fn find_remote_branch(git: &dyn GitBackend, path: &Path, branch: &str) -> Option<String> {
+    let matching: Vec<String> = git
+        .remote_branches(path)
+        .into_iter()
+        .filter(|remote| {
+            remote
+                .split_once('/')
+                .is_some_and(|(_, name)| name == branch)
+        })
+        .collect();
+
+    let origin = format!("origin/{}", branch);
+    matching
+        .iter()
+        .find(|remote| **remote == origin)
+        .or(matching.first())
+        .cloned()
+}
+
+/// Outcome and first error line of a failed git operation.
+pub(super) fn failure(output: &GitOutput) -> (Outcome, String) {
+    let message = output
+        .stderr
+        .lines()
+        .chain(output.stdout.lines())
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("git failed")
+        .trim()
+        .to_string();
+    (Outcome::of(output), message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fake::FakeRepo;
+    use crate::testing::Fixture;
+
+    #[test]
+    fn checkout_switches_and_tracks_remote_only_branches() {
+        let fixture = Fixture::new(
+            "checkout",
+            [
+                ("local", FakeRepo::new("main").with_branch("feature")),
+                (
+                    "remote",
+                    FakeRepo::new("main")
+                        .with_remote_branch("upstream/feature")
+                        .with_remote_branch("origin/feature"),
+                ),
+            ],
+        );
+        let (git, local, remote) = (&fixture.git, &fixture.paths[0], &fixture.paths[1]);
+
+        checkout_branch(
+            &fixture.config,
+            &Selection::default(),
+            git,
+            "feature",
+            false,
+        );
+
+        assert_eq!(git.repo(local).unwrap().branch.as_deref(), Some("feature"));
+        assert_eq!(git.repo(remote).unwrap().branch.as_deref(), Some("feature"));
+        assert!(git.calls().contains(&format!(
+            "create_branch {} feature origin/feature",
+            remote.display()
+        )));
+    }
+
+    #[test]
+    fn checkout_leaves_dirty_repositories_alone_without_stash() {
+        let fixture = Fixture::new(
+            "checkout-dirty",
+            [(
+                "app",
+                FakeRepo::new("main")
+                    .with_branch("feature")
+                    .with_change(" M", "src/main.rs"),
+            )],
+        );
+
+        checkout_branch(
+            &fixture.config,
+            &Selection::default(),
+            &fixture.git,
+            "feature",
+            false,
+        );
+
+        let state = fixture.git.repo(&fixture.paths[0]).unwrap();
+        assert_eq!(state.branch.as_deref(), Some("main"));
+        assert_eq!(state.changes.len(), 1);
+        assert!(state.stashes.is_empty());
+    }
+
+    #[test]
+    fn failed_checkout_restores_stashed_changes() {
+        let fixture = Fixture::new(
+            "checkout-fails",
+            [(
+                "app",
+                FakeRepo::new("main")
+                    .with_branch("feature")
+                    .with_change(" M", "src/main.rs")
+                    .failing("checkout", "error: Your local changes would be overwritten"),
+            )],
+        );
+        let (git, dir) = (&fixture.git, &fixture.paths[0]);
+
+        checkout_branch(&fixture.config, &Selection::default(), git, "feature", true);
+
+        let state = git.repo(dir).unwrap();
+        assert_eq!(state.branch.as_deref(), Some("main"));
+        assert!(state.stashes.is_empty());
+        assert!(
+            git.calls()
+                .contains(&format!("stash_pop {} 0", dir.display()))
+        );
+    }
+
+    #[test]
+    fn create_branch_skips_repositories_that_have_it() {
+        let fixture = Fixture::new(
+            "create-branch",
+            [
+                ("fresh", FakeRepo::new("main")),
+                ("existing", FakeRepo::new("main").with_branch("feature")),
+            ],
+        );
+        let (git, fresh, existing) = (&fixture.git, &fixture.paths[0], &fixture.paths[1]);
+
+        create_branch(
+            &fixture.config,
+            &Selection::default(),
+            git,
+            "feature",
+            None,
+            false,
+        );
+
+        assert_eq!(git.repo(fresh).unwrap().branch.as_deref(), Some("feature"));
+        assert_eq!(git.repo(existing).unwrap().branch.as_deref(), Some("main"));
+        assert!(
+            git.calls()
+                .contains(&format!("create_branch {} feature HEAD", fresh.display()))
+        );
+    }
+
+    /// On `current`, with branches merged, gone, both or neither, and
+    /// protected ones that are merged too. Returns the remaining branches and
+    /// the deletions attempted.
+    fn pruned(test: &str, force: bool) -> (Vec<String>, Vec<String>) {
+        let mut repo = FakeRepo::new("current")
+            .with_branch("main")
+            .with_branch("develop")
+            .with_branch("merged")
+            .with_branch("gone")
+            .with_branch("squashed")
+            .with_branch("wip");
+        repo.merged = ["current", "main", "develop", "merged", "gone"]
+            .map(String::from)
+            .to_vec();
+        repo.gone = vec!["gone".to_string(), "squashed".to_string()];
+        repo.unpushed.insert(
+            "squashed".to_string(),
+            vec![Commit {
+                sha: "3".repeat(40),
+                summary: "Fix typo".to_string(),
+            }],
+        );
+        let fixture = Fixture::new(test, [("app", repo)]);
+        let (git, dir) = (&fixture.git, &fixture.paths[0]);
+
+        prune_branches(&fixture.config, &Selection::default(), git, true, force);
+
+        let prefix = format!("delete_branch {} ", dir.display());
+        let deletions = git
+            .calls()
+            .iter()
+            .filter_map(|call| call.strip_prefix(&prefix).map(str::to_string))
+            .collect();
+        (git.repo(dir).unwrap().branches, deletions)
+    }
+
+    #[test]
+    fn prune_keeps_current_protected_and_unmerged_branches() {
+        let (branches, deletions) = pruned("prune", false);
+        assert_eq!(branches, ["current", "main", "develop", "squashed", "wip"]);
+        // Merged into the default branch, which `-d` would not check against
+        assert_eq!(deletions, ["-D merged", "-D gone", "-d squashed"]);
+    }
+
+    #[test]
+    fn prune_deletes_unmerged_gone_branches_with_force() {
+        let (branches, deletions) = pruned("prune-force", true);
+        assert_eq!(branches, ["current", "main", "develop", "wip"]);
+        assert_eq!(deletions, ["-D merged", "-D gone", "-D squashed"]);
+    }
+
+    #[test]
+    fn prune_finds_the_default_branch_on_a_remote_configured_by_url() {
+        let url = "git@example.com:app.git";
+        let mut repo = FakeRepo::new("feature")
+            .with_branch("done")
+            .with_remote("upstream", url)
+            .with_remote_branch("upstream/main");
+        repo.merged = vec!["done".to_string()];
+        let mut fixture = Fixture::new("prune-remote", [("app", repo)]);
+        fixture.config.repositories[0].remote = Some(url.to_string());
+
+        prune_branches(
+            &fixture.config,
+            &Selection::default(),
+            &fixture.git,
+            true,
+            false,
+        );
+
+        let state = fixture.git.repo(&fixture.paths[0]).unwrap();
+        assert_eq!(state.branches, ["feature"]);
+    }
+
+    #[test]
+    fn checkout_ignores_untracked_files_and_restores_stashed_changes() {
+        let fixture = Fixture::new(
+            "checkout-stash",
+            [
+                (
+                    "untracked",
+                    FakeRepo::new("main")
+                        .with_branch("feature")
+                        .with_change("??", "notes.txt"),
+                ),
+                (
+                    "modified",
+                    FakeRepo::new("main")
+                        .with_branch("feature")
+                        .with_change(" M", "src/main.rs"),
+                ),
+            ],
+        );
+        let (git, untracked, modified) = (&fixture.git, &fixture.paths[0], &fixture.paths[1]);
+
+        checkout_branch(&fixture.config, &Selection::default(), git, "feature", true);
+
+        for path in [untracked, modified] {
+            let state = git.repo(path).unwrap();
+            assert_eq!(state.branch.as_deref(), Some("feature"));
+            assert!(state.stashes.is_empty());
+        }
+        assert!(
+            !git.calls()
+                .iter()
+                .any(|call| call.starts_with(&format!("stash {}", untracked.display())))
+        );
+        assert!(
+            git.calls()
+                .contains(&format!("stash_pop {} 0", modified.display()))
+        );
+    }
+}
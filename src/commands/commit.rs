@@ -0,0 +1,220 @@
+use super::branch::{failure, for_each_repository};
+use super::summary::Outcome;
+use crate::config::{Config, Selection, select_repositories};
+use crate::git::GitBackend;
+use colored::*;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+
+/// Trailer linking the commits made by one `gitpower commit --change-set`.
+const CHANGE_SET_TRAILER: &str = "Change-Set";
+
+/// How the changes to commit are chosen.
+#[derive(Debug, Clone, Default)]
+pub struct CommitSettings {
+    /// Commit all changes to tracked files instead of only the staged ones
+    pub all: bool,
+    /// Trailers added to every commit, as `Key: value`
+    pub trailers: Vec<String>,
+    /// Add a generated `Change-Set` trailer
+    pub change_set: bool,
+}
+
+/// Commits in every selected repository that has something to commit, with
+/// the same message, and lists the resulting commits.
+pub fn commit_repositories(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    message: &str,
+    settings: &CommitSettings,
+) {
+    if let Some(invalid) = settings.trailers.iter().find(|t| !is_trailer(t)) {
+        eprintln!(
+            "{} Invalid trailer '{}': expected 'Key: value'",
+            "ERROR:".red().bold(),
+            invalid
+        );
+        return;
+    }
+
+    let repos = select_repositories(config, selection);
+    if repos.is_empty() {
+        println!("No repositories to commit in.");
+        return;
+    }
+
+    let mut trailers = settings.trailers.clone();
+    if settings.change_set {
+        match change_set_id() {
+            Ok(id) => trailers.push(format!("{}: {}", CHANGE_SET_TRAILER, id)),
+            Err(e) => {
+                eprintln!(
+                    "{} Failed to generate a change set id: {}",
+                    "ERROR:".red().bold(),
+                    e
+                );
+                return;
+            }
+        }
+    }
+    let message = if trailers.is_empty() {
+        message.to_string()
+    } else {
+        format!("{}\n\n{}", message.trim_end(), trailers.join("\n"))
+    };
+
+    println!("{}", "Committing changes:".green().bold());
+    let commits = RefCell::new(Vec::new());
+    let summary = for_each_repository(&repos, |repo, path| {
+        let changes = match git.status(path) {
+            Ok(changes) => changes,
+            Err(e) => return (Outcome::Failed, e),
+        };
+        let count = if settings.all {
+            changes.iter().filter(|c| c.code != "??").count()
+        } else {
+            // The first letter of the code is the state in the index
+            changes
+                .iter()
+                .filter(|c| !c.code.starts_with([' ', '?']))
+                .count()
+        };
+        if count == 0 {
+            let reason = if settings.all {
+                "no changes to tracked files"
+            } else {
+                "nothing staged"
+            };
+            return (Outcome::Skipped, reason.to_string());
+        }
+
+        let output = if settings.all {
+            git.commit_tracked(path, &message)
+        } else {
+            git.commit(path, &message)
+        };
+        if !output.success {
+            return failure(&output);
+        }
+
+        let plural = if count == 1 { "" } else { "s" };
+        match git.log(path, 1).into_iter().next() {
+            Some(commit) => {
+                let text = format!(
+                    "committed {} ({} file{})",
+                    &commit.sha[..commit.sha.len().min(7)],
+                    count,
+                    plural
+                );
+                commits.borrow_mut().push((repo.name.clone(), commit.sha));
+                (Outcome::Ok, text)
+            }
+            None => (Outcome::Ok, format!("committed {} file{}", count, plural)),
+        }
+    });
+    summary.print("Commit complete!");
+
+    let commits = commits.into_inner();
+    if !commits.is_empty() {
+        println!("\n{}", "Commits:".bold());
+        let width = commits
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (name, sha) in &commits {
+            println!("  {:width$}  {}", name, sha.yellow(), width = width);
+        }
+    }
+    if let Some(change_set) = trailers.iter().find(|t| t.starts_with(CHANGE_SET_TRAILER)) {
+        println!("{}", change_set);
+    }
+}
+
+/// `Key: value`, with a key git recognizes as a trailer token.
+fn is_trailer(trailer: &str) -> bool {
+    trailer.split_once(':').is_some_and(|(key, value)| {
+        !key.is_empty()
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !value.trim().is_empty()
+    })
+}
+
+/// A random (version 4) UUID.
+fn change_set_id() -> std::io::Result<String> {
+    let mut bytes = [0u8; 16];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fake::FakeRepo;
+    use crate::testing::Fixture;
+
+    #[test]
+    fn recognizes_trailers() {
+        assert!(is_trailer("Refs: #123"));
+        assert!(is_trailer("Co-reviewed-by: Sam <sam@example.com>"));
+        assert!(!is_trailer("Refs #123"));
+        assert!(!is_trailer("Refs:"));
+        assert!(!is_trailer("Not a key: value"));
+    }
+
+    #[test]
+    fn change_set_ids_are_version_4_uuids() {
+        let id = change_set_id().unwrap();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!("89ab".contains(&id[19..20]), "{}", id);
+        assert_ne!(id, change_set_id().unwrap());
+    }
+
+    #[test]
+    fn commits_staged_or_tracked_changes_with_trailers() {
+        let fixture = Fixture::new(
+            "commit",
+            [
+                ("staged", FakeRepo::new("main").with_change("M ", "a.rs")),
+                ("tracked", FakeRepo::new("main").with_change(" M", "b.rs")),
+                ("untracked", FakeRepo::new("main").with_change("??", "c.rs")),
+            ],
+        );
+        let (config, git) = (&fixture.config, &fixture.git);
+        let [staged, tracked, untracked] = [0, 1, 2].map(|i| &fixture.paths[i]);
+        let settings = CommitSettings {
+            trailers: vec!["Refs: #42".to_string()],
+            ..CommitSettings::default()
+        };
+
+        commit_repositories(config, &Selection::default(), git, "Bump", &settings);
+        let message = "Bump\n\nRefs: #42".to_string();
+        assert!(
+            git.calls()
+                .contains(&format!("commit {} {}", staged.display(), message))
+        );
+        assert!(git.repo(tracked).unwrap().commits.is_empty());
+
+        let settings = CommitSettings {
+            all: true,
+            ..settings
+        };
+        commit_repositories(config, &Selection::default(), git, "Bump", &settings);
+        assert_eq!(git.repo(tracked).unwrap().commits[0].summary, "Bump");
+        assert!(git.repo(untracked).unwrap().commits.is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{
+    CURRENT_VERSION, Config, ConfigFormat, ConfigLock, backup_path, migrate, read_config,
+    save_document, write_config_file,
+};
+
+pub fn migrate_config(config_path: &Path) {
+    let _lock = match ConfigLock::acquire(config_path) {
+        Ok(lock) => lock,
+        Err(e) => {
+            println!("{} {}", "ERROR:".red().bold(), e);
+            return;
+        }
+    };
+
+    let format = match ConfigFormat::from_path(config_path) {
+        Ok(format) => format,
+        Err(e) => {
+            println!("{} {}", "ERROR:".red().bold(), e);
+            return;
+        }
+    };
+
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) => {
+            println!(
+                "{} Failed to read config '{}': {}",
+                "ERROR:".red().bold(),
+                config_path.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    let mut doc: serde_yaml::Value = match format.parse(&content) {
+        Ok(doc) => doc,
+        Err(e) => {
+            println!("{} Failed to parse config: {}", "ERROR:".red().bold(), e);
+            return;
+        }
+    };
+
+    let from = match migrate::migrate(&mut doc) {
+        Ok(from) => from,
+        Err(e) => {
+            println!("{} {}", "ERROR:".red().bold(), e);
+            return;
+        }
+    };
+
+    if from == CURRENT_VERSION {
+        println!(
+            "Config is already at schema version {}, nothing to migrate",
+            CURRENT_VERSION
+        );
+        return;
+    }
+
+    // Make sure the upgraded document still describes a valid config before touching the file
+    if let Err(e) = serde_yaml::from_value::<Config>(doc.clone()) {
+        println!(
+            "{} Migrated config is invalid: {}",
+            "ERROR:".red().bold(),
+            e
+        );
+        return;
+    }
+
+    if let Err(e) = save_document(&doc, config_path) {
+        println!("{} Failed to save config: {}", "ERROR:".red().bold(), e);
+        return;
+    }
+
+    println!(
+        "{} Migrated config from version {} to {} (backup saved to {})",
+        "SUCCESS:".green().bold(),
+        from,
+        CURRENT_VERSION,
+        backup_path(config_path).display()
+    );
+}
+
+pub fn convert_config(config_path: &Path, to: &str, output: Option<&str>, force: bool) {
+    let format = ConfigFormat::from_name(to).expect("format is validated by clap");
+
+    let output_path = match output {
+        Some(path) => PathBuf::from(shellexpand::tilde(path).as_ref()),
+        None => config_path.with_extension(format.extension()),
+    };
+
+    match ConfigFormat::from_path(&output_path) {
+        Ok(output_format) if output_format == format => {}
+        Ok(output_format) => {
+            println!(
+                "{} Output file '{}' has a {} extension but {} was requested",
+                "ERROR:".red().bold(),
+                output_path.display(),
+                output_format,
+                format
+            );
+            return;
+        }
+        Err(e) => {
+            println!("{} {}", "ERROR:".red().bold(), e);
+            return;
+        }
+    }
+
+    if output_path == config_path {
+        println!("Config is already in {} format", format);
+        return;
+    }
+
+    if output_path.exists() && !force {
+        println!(
+            "{} '{}' already exists, use --force to overwrite it",
+            "ERROR:".red().bold(),
+            output_path.display()
+        );
+        return;
+    }
+
+    let config = match read_config(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!(
+                "{} Cannot read {}: {}",
+                "ERROR:".red().bold(),
+                config_path.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    let content = match format.serialize(&config) {
+        Ok(content) => content,
+        Err(e) => {
+            println!(
+                "{} Failed to serialize config as {}: {}",
+                "ERROR:".red().bold(),
+                format,
+                e
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = write_config_file(&output_path, &content) {
+        println!("{} Failed to write config: {}", "ERROR:".red().bold(), e);
+        return;
+    }
+
+    println!(
+        "{} Converted config to {} at {}",
+        "SUCCESS:".green().bold(),
+        format,
+        output_path.display()
+    );
+    println!("Use '--config {}' to load it.", output_path.display());
+}
+
+pub fn print_config_schema(output: Option<&str>) {
+    let schema = schemars::schema_for!(Config);
+    let json = match serde_json::to_string_pretty(&schema) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!(
+                "{} Failed to serialize schema: {}",
+                "ERROR:".red().bold(),
+                e
+            );
+            return;
+        }
+    };
+
+    match output {
+        Some(path) => {
+            let path = shellexpand::tilde(path);
+            if let Err(e) = fs::write(path.as_ref(), json + "\n") {
+                eprintln!("{} Failed to write schema: {}", "ERROR:".red().bold(), e);
+                return;
+            }
+            println!(
+                "{} Wrote config schema to {}",
+                "SUCCESS:".green().bold(),
+                path
+            );
+        }
+        None => println!("{}", json),
+    }
+}
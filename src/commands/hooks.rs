@@ -0,0 +1,104 @@
+use super::summary::Outcome;
+use crate::config::{Config, Hook, Repository};
+use crate::git::GitOutput;
+use crate::process::{self, Interrupt, Stream};
+use colored::*;
+use std::path::Path;
+use std::process::Command as ProcessCommand;
+use std::sync::Mutex;
+
+/// Runs the `hook` commands configured for `repo` in `repo_path`, passing
+/// every line they print to `on_line`. Stops at the first command that fails
+/// and returns its outcome.
+pub fn run_hooks(
+    config: &Config,
+    repo: &Repository,
+    repo_path: &Path,
+    hook: Hook,
+    on_line: &(dyn Fn(&str) + Sync),
+) -> Outcome {
+    for command in config.hooks_for(repo, hook) {
+        on_line(&format!(
+            "{} {}: {}",
+            "HOOK:".cyan().bold(),
+            hook.name(),
+            command
+        ));
+
+        let mut child = ProcessCommand::new("sh");
+        child
+            .arg("-c")
+            .arg(command)
+            .current_dir(repo_path)
+            .env("GITPOWER_HOOK", hook.name())
+            .env("GITPOWER_REPO_NAME", &repo.name)
+            .env("GITPOWER_REPO_PATH", repo_path)
+            .env("GITPOWER_GROUPS", config.groups_of(repo).join(","));
+
+        let handler = |_: Stream, line: &str| on_line(line);
+        match process::run_streaming(child, config.timeout_for(repo), &handler) {
+            Ok(finished) => match finished.interrupted {
+                Some(Interrupt::TimedOut(limit)) => {
+                    on_line(&format!(
+                        "{} {} hook timed out after {}s",
+                        "TIMEOUT:".magenta().bold(),
+                        hook.name(),
+                        limit.as_secs()
+                    ));
+                    return Outcome::TimedOut(limit);
+                }
+                Some(Interrupt::Cancelled) => return Outcome::Cancelled,
+                None if !finished.status.success() => {
+                    on_line(&format!(
+                        "{} {} hook failed with code {}",
+                        "ERROR:".red().bold(),
+                        hook.name(),
+                        finished.status.code().unwrap_or(-1)
+                    ));
+                    return Outcome::Failed;
+                }
+                None => {}
+            },
+            Err(e) => {
+                on_line(&format!(
+                    "{} Failed to run {} hook: {}",
+                    "ERROR:".red().bold(),
+                    hook.name(),
+                    e
+                ));
+                return Outcome::Failed;
+            }
+        }
+    }
+    Outcome::Ok
+}
+
+/// Runs hooks with their output collected, for operations shown behind a
+/// progress display. A failure is returned as a failed `GitOutput` carrying
+/// the hooks' output.
+pub fn run_hooks_quietly(
+    config: &Config,
+    repo: &Repository,
+    repo_path: &Path,
+    hook: Hook,
+) -> Result<(), GitOutput> {
+    let collected = Mutex::new(String::new());
+    let outcome = run_hooks(config, repo, repo_path, hook, &|line| {
+        let mut collected = collected.lock().unwrap();
+        collected.push_str(line);
+        collected.push('\n');
+    });
+
+    let interrupted = match outcome {
+        Outcome::Ok => return Ok(()),
+        Outcome::TimedOut(limit) => Some(Interrupt::TimedOut(limit)),
+        Outcome::Cancelled => Some(Interrupt::Cancelled),
+        _ => None,
+    };
+    Err(GitOutput {
+        success: false,
+        stdout: String::new(),
+        stderr: collected.into_inner().unwrap(),
+        interrupted,
+    })
+}
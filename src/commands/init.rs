@@ -4,9 +4,9 @@ use std::fs;
 use std::path::Path;
 
 use crate::config::{Config, Group, Repository};
-use crate::git::run_git_command_with_output;
+use crate::git::{GitBackend, print_output};
 
-pub fn init_repository_interactive(config: &mut Config) {
+pub fn init_repository_interactive(config: &mut Config, git: &dyn GitBackend) {
     let theme = ColorfulTheme::default();
 
     println!(
@@ -104,27 +104,49 @@ pub fn init_repository_interactive(config: &mut Config) {
         None
     };
 
+    // Repository Tags
+    println!("\n{}", "Repository Tags".cyan().bold());
+    let tags = if Confirm::with_theme(&theme)
+        .with_prompt("Would you like to tag this repository (e.g., lang:rust, team:payments)?")
+        .interact()
+        .unwrap()
+    {
+        Some(
+            Input::<String>::with_theme(&theme)
+                .with_prompt("Enter tags (comma-separated)")
+                .interact_text()
+                .unwrap(),
+        )
+    } else {
+        None
+    };
+
     // Initialize the repository
     println!("\n{}", "Initializing repository...".cyan().bold());
     init_repository(
         config,
+        git,
         &name,
         &path,
         remote.as_deref(),
         branch.as_deref(),
         groups.as_deref(),
+        tags.as_deref(),
     );
 
     println!("\n{}", "✨ Repository setup complete!".green().bold());
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn init_repository(
     config: &mut Config,
+    git: &dyn GitBackend,
     name: &str,
     path: &str,
     remote: Option<&str>,
     branch: Option<&str>,
     groups: Option<&str>,
+    tags: Option<&str>,
 ) {
     let expanded_path = shellexpand::tilde(path);
     let repo_path = Path::new(expanded_path.as_ref());
@@ -154,7 +176,7 @@ pub fn init_repository(
 
     // Initialize git repository if it doesn't exist
     if !repo_path.join(".git").exists() {
-        if !run_git_command_with_output(repo_path, &["init"]) {
+        if !print_output(&git.init(repo_path)) {
             println!(
                 "{} Failed to initialize git repository",
                 "ERROR:".red().bold()
@@ -166,7 +188,7 @@ pub fn init_repository(
 
     // Add remote if provided
     if let Some(remote_url) = remote {
-        if !run_git_command_with_output(repo_path, &["remote", "add", "origin", remote_url]) {
+        if !print_output(&git.add_remote(repo_path, "origin", remote_url)) {
             println!("{} Failed to add remote", "ERROR:".red().bold());
             return;
         }
@@ -180,6 +202,15 @@ pub fn init_repository(
         remote: remote.map(|s| s.to_string()),
         branch: branch.map(|s| s.to_string()),
         groups: None,
+        tags: tags.map(|tags| {
+            tags.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        }),
+        timeout: None,
+        ssh_command: None,
+        hooks: None,
     };
 
     // Handle groups
@@ -201,12 +232,14 @@ pub fn init_repository(
                     groups.push(Group {
                         name: group_name.clone(),
                         repositories: vec![name.to_string()],
+                        hooks: None,
                     });
                 }
             } else {
                 config.groups = Some(vec![Group {
                     name: group_name.clone(),
                     repositories: vec![name.to_string()],
+                    hooks: None,
                 }]);
             }
         }
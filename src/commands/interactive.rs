@@ -15,22 +15,22 @@ use ratatui::{
 use std::io;
 use std::path::Path;
 
-use crate::config::Config;
-use crate::git::run_git_command;
+use crate::config::{Config, Selection, select_repositories};
+use crate::git::GitBackend;
 
-pub struct App {
+pub struct App<'a> {
     repositories: Vec<String>,
     selected_index: usize,
     status_text: String,
     list_state: ListState,
     config: Config,
+    git: &'a dyn GitBackend,
 }
 
-impl App {
-    pub fn new(config: Config) -> Self {
-        let repositories = config
-            .repositories
-            .iter()
+impl<'a> App<'a> {
+    pub fn new(config: Config, selection: &Selection, git: &'a dyn GitBackend) -> Self {
+        let repositories = select_repositories(&config, selection)
+            .into_iter()
             .map(|repo| repo.name.clone())
             .collect();
         Self {
@@ -39,6 +39,7 @@ impl App {
             status_text: String::new(),
             list_state: ListState::default().with_selected(Some(0)),
             config,
+            git,
         }
     }
 
@@ -176,27 +177,23 @@ impl App {
     }
 
     fn handle_events(&mut self) -> Result<bool> {
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => return Ok(true),
-                    KeyCode::Up => {
-                        if self.selected_index > 0 {
-                            self.selected_index -= 1;
-                            self.list_state.select(Some(self.selected_index));
-                        }
-                    }
-                    KeyCode::Down => {
-                        if self.selected_index < self.repositories.len() - 1 {
-                            self.selected_index += 1;
-                            self.list_state.select(Some(self.selected_index));
-                        }
-                    }
-                    KeyCode::Enter => {
-                        self.show_repository_status(self.selected_index);
-                    }
-                    _ => {}
+        if event::poll(std::time::Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+        {
+            match key.code {
+                KeyCode::Char('q') => return Ok(true),
+                KeyCode::Up if self.selected_index > 0 => {
+                    self.selected_index -= 1;
+                    self.list_state.select(Some(self.selected_index));
+                }
+                KeyCode::Down if self.selected_index + 1 < self.repositories.len() => {
+                    self.selected_index += 1;
+                    self.list_state.select(Some(self.selected_index));
+                }
+                KeyCode::Enter if !self.repositories.is_empty() => {
+                    self.show_repository_status(self.selected_index);
                 }
+                _ => {}
             }
         }
         Ok(false)
@@ -219,20 +216,21 @@ impl App {
         }
 
         // Get current branch
-        let branch_output = run_git_command(repo_path, &["branch", "--show-current"]);
-        let current_branch = String::from_utf8_lossy(&branch_output.stdout)
-            .trim()
-            .to_string();
+        let current_branch = self.git.current_branch(repo_path).unwrap_or_default();
 
         // Get status
-        let status_output = run_git_command(repo_path, &["status", "--porcelain"]);
-        let status_text = String::from_utf8_lossy(&status_output.stdout);
-        let has_changes = !status_text.trim().is_empty();
-
-        // Get remote info (only first line)
-        let remote_output = run_git_command(repo_path, &["remote", "-v"]);
-        let remote_info = String::from_utf8_lossy(&remote_output.stdout);
-        let remote_url = remote_info.lines().next().unwrap_or("No remote");
+        let has_changes = self
+            .git
+            .status(repo_path)
+            .is_ok_and(|changes| !changes.is_empty());
+
+        // Get remote info (only first remote)
+        let remote_url = self
+            .git
+            .remotes(repo_path)
+            .first()
+            .map(|remote| format!("{}\t{}", remote.name, remote.url))
+            .unwrap_or_else(|| "No remote".to_string());
 
         self.status_text = format!(
             "Repository: {}\nBranch: {} | Changes: {}\nRemote: {}",
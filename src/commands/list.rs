@@ -1,9 +1,9 @@
-use crate::config::Config;
+use crate::config::{Config, Selection, select_repositories};
 use colored::*;
 
-pub fn list_repositories(config: &Config) {
+pub fn list_repositories(config: &Config, selection: &Selection) {
     println!("{}", "Configured Repositories:".green().bold());
-    for repo in &config.repositories {
+    for repo in select_repositories(config, selection) {
         let path = shellexpand::tilde(&repo.path);
         let repo_path = std::path::Path::new(path.as_ref());
 
@@ -25,6 +25,9 @@ pub fn list_repositories(config: &Config) {
         if let Some(groups) = &repo.groups {
             println!("    Groups: {}", groups.join(", "));
         }
+        if let Some(tags) = &repo.tags {
+            println!("    Tags: {}", tags.join(", ").cyan());
+        }
     }
 
     if let Some(groups) = &config.groups {
@@ -1,13 +1,39 @@
+pub mod branch;
+pub mod commit;
+pub mod config;
+mod hooks;
 pub mod init;
 pub mod interactive;
 pub mod list;
+mod output;
+mod parallel;
+mod placeholders;
+pub mod plugin;
+mod progress;
 pub mod run;
+pub mod runs;
+pub mod snapshot;
+pub mod stash;
 pub mod status;
+mod summary;
 pub mod sync;
+pub mod tag;
+mod time;
+pub mod workflow;
 
+pub use branch::*;
+pub use commit::*;
+pub use config::*;
 pub use init::*;
 pub use interactive::*;
 pub use list::*;
+pub use output::OutputMode;
+pub use plugin::*;
 pub use run::*;
+pub use runs::*;
+pub use snapshot::*;
+pub use stash::*;
 pub use status::*;
 pub use sync::*;
+pub use tag::*;
+pub use workflow::*;
@@ -0,0 +1,92 @@
+use crate::config::Repository;
+use colored::*;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// How the output of commands running in several repositories is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Print lines as they arrive, prefixed with the repository name
+    Stream,
+    /// Collect each repository's output and print it in one block when it finishes
+    Group,
+}
+
+impl OutputMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "stream" => Some(OutputMode::Stream),
+            "group" => Some(OutputMode::Group),
+            _ => None,
+        }
+    }
+}
+
+/// Colors cycled through so neighbouring repositories are easy to tell apart.
+const PREFIX_COLORS: [Color; 10] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::BrightCyan,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+];
+
+/// Output of one repository, shared by the threads reading its child.
+pub struct RepoOutput {
+    mode: OutputMode,
+    prefix: String,
+    header: String,
+    buffer: Mutex<String>,
+}
+
+impl RepoOutput {
+    /// `index` picks the prefix color; `width` is the longest repository
+    /// name, so streamed lines line up.
+    pub fn new(mode: OutputMode, repo: &Repository, index: usize, width: usize) -> Self {
+        let color = PREFIX_COLORS[index % PREFIX_COLORS.len()];
+        Self {
+            mode,
+            prefix: format!("{:width$} |", repo.name, width = width)
+                .color(color)
+                .to_string(),
+            header: format!("\n{} ({})", repo.name.yellow().bold(), repo.path),
+            buffer: Mutex::new(String::new()),
+        }
+    }
+
+    pub fn line(&self, line: &str) {
+        match self.mode {
+            OutputMode::Stream => println!("{} {}", self.prefix, line),
+            OutputMode::Group => self.buffer(line),
+        }
+    }
+
+    pub fn error_line(&self, line: &str) {
+        match self.mode {
+            OutputMode::Stream => eprintln!("{} {}", self.prefix, line),
+            OutputMode::Group => self.buffer(line),
+        }
+    }
+
+    /// Prints the collected block in group mode; streamed output is already out.
+    pub fn finish(&self) {
+        if self.mode == OutputMode::Group {
+            let buffer = self.buffer.lock().unwrap();
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", self.header);
+            let _ = write!(stdout, "{}", buffer);
+        }
+    }
+
+    fn buffer(&self, line: &str) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.push_str("  ");
+        buffer.push_str(line);
+        buffer.push('\n');
+    }
+}
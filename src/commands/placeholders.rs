@@ -0,0 +1,125 @@
+/// Per-repository values substituted into `gitpower run` commands.
+///
+/// `{name}`, `{path}`, `{branch}`, `{remote}` and `{groups}` are replaced with
+/// the repository's values, `{tag:KEY}` with the value of its `KEY:value`
+/// tag. Anything else in braces is left alone, as is `${...}`, so shell
+/// syntax such as `${HOME}`, `{a,b}` or `find -exec ... {}` keeps working.
+pub struct Placeholders<'a> {
+    pub name: &'a str,
+    pub path: &'a str,
+    pub branch: &'a str,
+    pub remote: &'a str,
+    pub groups: &'a [&'a str],
+    pub tags: &'a [String],
+}
+
+impl Placeholders<'_> {
+    /// Replaces placeholders in `template`, passing every value through
+    /// `quote` first together with the text expanded before it. Fails when a
+    /// `{tag:KEY}` has no matching tag.
+    pub fn expand(
+        &self,
+        template: &str,
+        quote: impl Fn(&str, &str) -> String,
+    ) -> Result<String, String> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            expanded.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+
+            let key = after
+                .find('}')
+                .map(|close| &after[..close])
+                .filter(|key| !key.is_empty() && !key.contains(['{', ' ', '\t', '\n']));
+            let escaped = expanded.ends_with('$');
+
+            match key.filter(|_| !escaped).map(|key| (key, self.value(key))) {
+                Some((key, Some(value))) => {
+                    let quoted = quote(&expanded, &value?);
+                    expanded.push_str(&quoted);
+                    rest = &after[key.len() + 1..];
+                }
+                _ => {
+                    expanded.push('{');
+                    rest = after;
+                }
+            }
+        }
+
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// `None` when `key` is not a placeholder.
+    fn value(&self, key: &str) -> Option<Result<String, String>> {
+        let value = match key {
+            "name" => self.name.to_string(),
+            "path" => self.path.to_string(),
+            "branch" => self.branch.to_string(),
+            "remote" => self.remote.to_string(),
+            "groups" => self.groups.join(","),
+            _ => {
+                let tag_key = key.strip_prefix("tag:")?;
+                return Some(
+                    self.tags
+                        .iter()
+                        .find_map(|tag| tag.strip_prefix(tag_key)?.strip_prefix(':'))
+                        .map(str::to_string)
+                        .ok_or_else(|| {
+                            format!("No '{}:' tag for placeholder {{{}}}", tag_key, key)
+                        }),
+                );
+            }
+        };
+        Some(Ok(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(template: &str) -> Result<String, String> {
+        let tags = ["lang:rust".to_string(), "team:pay:ments".to_string()];
+        Placeholders {
+            name: "api",
+            path: "/src/my api",
+            branch: "main",
+            remote: "origin",
+            groups: &["backend", "prod"],
+            tags: &tags,
+        }
+        .expand(template, |_, value| format!("<{}>", value))
+    }
+
+    #[test]
+    fn replaces_known_placeholders_through_quote() {
+        assert_eq!(
+            expand("cd {path} && echo {name}@{remote}/{branch} {groups}").unwrap(),
+            "cd </src/my api> && echo <api>@<origin>/<main> <backend,prod>"
+        );
+    }
+
+    #[test]
+    fn replaces_tag_values() {
+        assert_eq!(expand("{tag:lang}").unwrap(), "<rust>");
+        assert_eq!(expand("{tag:team}").unwrap(), "<pay:ments>");
+        let error = expand("{tag:owner}").unwrap_err();
+        assert!(error.contains("'owner:'"), "{}", error);
+    }
+
+    #[test]
+    fn leaves_shell_syntax_alone() {
+        for template in [
+            "${HOME}/{name",
+            "echo {a,b} {} { name } {unknown}",
+            r"find . -exec rm {} \;",
+            "}{",
+        ] {
+            assert_eq!(expand(template).unwrap(), template);
+        }
+        assert_eq!(expand("${name}{name}").unwrap(), "${name}<api>");
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::{Config, Selection, TagFilter, select_repositories};
+use colored::*;
+use serde_json::json;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Prefix of the executables found on `PATH` that provide external subcommands.
+const PLUGIN_PREFIX: &str = "gitpower-";
+
+/// Plugin arguments with the repository selection gitpower handles itself taken out.
+#[derive(Debug, Default)]
+struct PluginArgs {
+    names: Vec<String>,
+    tags: Vec<String>,
+    not_tags: Vec<String>,
+    rest: Vec<OsString>,
+}
+
+/// Runs the plugin `program` with `args`, passing the config path and the
+/// selected repositories in its environment. Plugins configured with
+/// `selection` have repositories selected by their arguments, and those with
+/// `stdin` get the same information as JSON on stdin.
+/// Returns the exit code to exit with.
+pub fn run_plugin(
+    config: &Config,
+    config_path: &Path,
+    program: &Path,
+    name: &str,
+    args: &[OsString],
+) -> i32 {
+    let plugin = config.plugin(name);
+    let selection = plugin.and_then(|plugin| plugin.selection).unwrap_or(false);
+    let stdin = plugin.and_then(|plugin| plugin.stdin).unwrap_or(false);
+
+    let args = if selection {
+        match split_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("{} {}", "ERROR:".red().bold(), e);
+                return 1;
+            }
+        }
+    } else {
+        PluginArgs {
+            rest: args.to_vec(),
+            ..PluginArgs::default()
+        }
+    };
+
+    let selection = Selection {
+        names: args.names.iter().map(String::as_str).collect(),
+        tags: TagFilter::new(
+            args.tags.iter().map(String::as_str),
+            args.not_tags.iter().map(String::as_str),
+        ),
+    };
+    let repos = select_repositories(config, &selection);
+    let config_path = fs::canonicalize(config_path).unwrap_or_else(|_| config_path.to_path_buf());
+
+    let context = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "config_path": config_path,
+        "repositories": repos
+            .iter()
+            .map(|repo| json!({
+                "name": repo.name,
+                "path": shellexpand::tilde(&repo.path),
+                "remote": repo.remote,
+                "branch": repo.branch,
+                "groups": config.groups_of(repo),
+                "tags": repo.tags.as_deref().unwrap_or_default(),
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    let mut command = Command::new(program);
+    command
+        .args(&args.rest)
+        .env("GITPOWER_CONFIG", &config_path)
+        .env(
+            "GITPOWER_REPOS",
+            repos
+                .iter()
+                .map(|repo| repo.name.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    if stdin {
+        command.stdin(Stdio::piped());
+    }
+    if let Ok(exe) = env::current_exe() {
+        command.env("GITPOWER_BIN", exe);
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!(
+                "{} Failed to run {}: {}",
+                "ERROR:".red().bold(),
+                program.display(),
+                e
+            );
+            return 1;
+        }
+    };
+
+    // Plugins that don't read stdin close it early; that is fine
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "{}", context);
+    }
+
+    match child.wait() {
+        // Killed by a signal: report it the way a shell would
+        Ok(status) => status
+            .code()
+            .or_else(|| status.signal().map(|signal| 128 + signal))
+            .unwrap_or(1),
+        Err(e) => {
+            eprintln!(
+                "{} Failed to wait for {}: {}",
+                "ERROR:".red().bold(),
+                program.display(),
+                e
+            );
+            1
+        }
+    }
+}
+
+/// Finds the `gitpower-<name>` executable on `PATH`.
+pub fn find_plugin(name: &str) -> Result<PathBuf, String> {
+    let file = format!("{}{}", PLUGIN_PREFIX, name);
+    env::var_os("PATH")
+        .and_then(|path| {
+            env::split_paths(&path)
+                .map(|dir| dir.join(&file))
+                .find(|path| {
+                    fs::metadata(path)
+                        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+                })
+        })
+        .ok_or_else(|| {
+            format!(
+                "Unknown command '{}': no '{}' found on PATH. Try 'gitpower --help'.",
+                name, file
+            )
+        })
+}
+
+/// Takes `--repos`, `--tag`/`-t` and `--not-tag` out of `args`. Everything
+/// after a `--` is passed through untouched.
+fn split_args(args: &[OsString]) -> Result<PluginArgs, String> {
+    let mut parsed = PluginArgs::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let Some(text) = arg.to_str() else {
+            parsed.rest.push(arg.clone());
+            continue;
+        };
+        if text == "--" {
+            parsed.rest.extend(args.cloned());
+            break;
+        }
+
+        let (flag, inline) = match text.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (text, None),
+        };
+        let target = match flag {
+            "--repos" => &mut parsed.names,
+            "--tag" | "-t" => &mut parsed.tags,
+            "--not-tag" => &mut parsed.not_tags,
+            _ => {
+                parsed.rest.push(arg.clone());
+                continue;
+            }
+        };
+
+        let value = match inline {
+            Some(value) => value,
+            None => args
+                .next()
+                .and_then(|value| value.to_str())
+                .filter(|value| *value != "--")
+                .ok_or_else(|| format!("{} needs a value", flag))?
+                .to_string(),
+        };
+        if flag == "--repos" {
+            target.extend(
+                value
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty()),
+            );
+        } else {
+            target.push(value);
+        }
+    }
+
+    Ok(parsed)
+}
@@ -0,0 +1,266 @@
+use super::summary::Outcome;
+use crate::config::Repository;
+use crate::git::ProgressHandler;
+use crate::process;
+use colored::*;
+use crossterm::{cursor, queue, terminal};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Column width of the state label, fits the longest one ("auth failed").
+const STATE_WIDTH: usize = 11;
+
+/// How often the display is redrawn on a terminal.
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Queued,
+    Running(Instant),
+    Done(Outcome, Duration),
+}
+
+struct Row {
+    name: String,
+    state: State,
+    /// Latest progress message from git
+    detail: String,
+}
+
+/// Live per-repository status for batch git operations.
+///
+/// On a terminal every repository gets a line with a spinner that is redrawn
+/// in place, as many as fit on the screen; otherwise a plain line is printed
+/// when a repository starts and when it finishes.
+pub struct Progress {
+    rows: Mutex<Vec<Row>>,
+    width: usize,
+    tty: bool,
+    /// Lines the previous frame took up
+    drawn: AtomicUsize,
+}
+
+impl Progress {
+    pub fn new(repos: &[&Repository]) -> Arc<Self> {
+        Arc::new(Self {
+            rows: Mutex::new(
+                repos
+                    .iter()
+                    .map(|repo| Row {
+                        name: repo.name.clone(),
+                        state: State::Queued,
+                        detail: String::new(),
+                    })
+                    .collect(),
+            ),
+            width: repos
+                .iter()
+                .map(|repo| repo.name.chars().count())
+                .max()
+                .unwrap_or(0),
+            tty: std::io::stdout().is_terminal(),
+            drawn: AtomicUsize::new(0),
+        })
+    }
+
+    /// Runs `work`, keeping the display up to date until it returns.
+    pub fn show<R>(&self, work: impl FnOnce() -> R) -> R {
+        if !self.tty {
+            return work();
+        }
+
+        process::hold_interrupt_notice(|| {
+            let done = AtomicBool::new(false);
+            let _ = queue!(std::io::stdout(), cursor::Hide);
+            self.draw(0, false);
+
+            let result = thread::scope(|scope| {
+                scope.spawn(|| {
+                    let mut frame = 0;
+                    while !done.load(Ordering::SeqCst) {
+                        thread::sleep(FRAME_INTERVAL);
+                        frame += 1;
+                        self.draw(frame, false);
+                    }
+                });
+                let result = work();
+                done.store(true, Ordering::SeqCst);
+                result
+            });
+
+            self.draw(0, true);
+            let _ = queue!(std::io::stdout(), cursor::Show);
+            let _ = std::io::stdout().flush();
+            result
+        })
+    }
+
+    pub fn start(&self, index: usize) {
+        let mut rows = self.rows.lock().unwrap();
+        rows[index].state = State::Running(Instant::now());
+        if !self.tty {
+            println!("{} running", self.label(&rows[index].name));
+        }
+    }
+
+    pub fn finish(&self, index: usize, outcome: Outcome) {
+        let mut rows = self.rows.lock().unwrap();
+        let elapsed = match rows[index].state {
+            State::Running(started) => started.elapsed(),
+            _ => Duration::ZERO,
+        };
+        rows[index].state = State::Done(outcome, elapsed);
+        if !self.tty && outcome != Outcome::NotRun {
+            println!(
+                "{} {} ({})",
+                self.label(&rows[index].name),
+                outcome_label(outcome),
+                format_elapsed(elapsed)
+            );
+        }
+    }
+
+    /// Handler that shows git's progress messages on the repository's line.
+    pub fn reporter(self: &Arc<Self>, index: usize) -> Option<ProgressHandler> {
+        if !self.tty {
+            return None;
+        }
+        let progress = Arc::clone(self);
+        Some(Arc::new(move |update: &str| {
+            progress.rows.lock().unwrap()[index].detail = update.to_string();
+        }))
+    }
+
+    fn label(&self, name: &str) -> String {
+        format!("{:width$}", name, width = self.width)
+            .bold()
+            .to_string()
+    }
+
+    /// Draws over the previous frame. Only as many rows as fit on the screen
+    /// are shown, running ones first, unless this is the `last` frame, which
+    /// is not redrawn and may scroll.
+    fn draw(&self, frame: usize, last: bool) {
+        let rows = self.rows.lock().unwrap();
+        let (columns, lines) = terminal::size()
+            .ok()
+            .filter(|(columns, lines)| *columns > 0 && *lines > 0)
+            .map(|(columns, lines)| (columns as usize, lines as usize))
+            .unwrap_or((80, 24));
+        let mut stdout = std::io::stdout().lock();
+
+        let drawn = self.drawn.load(Ordering::SeqCst);
+        if drawn > 0 {
+            let _ = queue!(stdout, cursor::MoveUp(drawn as u16));
+        }
+
+        // Leave a line for the cursor, so the top of the block never scrolls away
+        let fits = lines.saturating_sub(1).max(2);
+        let mut shown: Vec<usize> = (0..rows.len()).collect();
+        if !last && rows.len() > fits {
+            shown.sort_by_key(|&index| match rows[index].state {
+                State::Running(_) => 0,
+                State::Queued => 1,
+                State::Done(..) => 2,
+            });
+            shown.truncate(fits - 1);
+            shown.sort();
+        }
+
+        for row in shown.iter().map(|&index| &rows[index]) {
+            let (icon, state, elapsed) = match row.state {
+                State::Queued => ("·".dimmed(), "queued".dimmed(), String::new()),
+                State::Running(started) => (
+                    SPINNER[frame % SPINNER.len()].cyan(),
+                    "running".cyan(),
+                    format_elapsed(started.elapsed()),
+                ),
+                State::Done(outcome, elapsed) => (
+                    outcome_icon(outcome),
+                    outcome_label(outcome),
+                    format_elapsed(elapsed),
+                ),
+            };
+
+            // Keep every row on a single terminal line so moving up stays accurate
+            let fixed = 2 + self.width + 1 + STATE_WIDTH + 1 + 6 + 2;
+            let detail: String = match row.state {
+                State::Running(_) => row
+                    .detail
+                    .chars()
+                    .take(columns.saturating_sub(fixed))
+                    .collect(),
+                _ => String::new(),
+            };
+
+            let _ = queue!(stdout, terminal::Clear(terminal::ClearType::CurrentLine));
+            let _ = writeln!(
+                stdout,
+                "{} {} {}{} {:>6}  {}",
+                icon,
+                self.label(&row.name),
+                state,
+                " ".repeat(STATE_WIDTH.saturating_sub(state.chars().count())),
+                elapsed,
+                detail.dimmed()
+            );
+        }
+
+        let hidden = rows.len() - shown.len();
+        if hidden > 0 {
+            let queued = rows
+                .iter()
+                .enumerate()
+                .filter(|(index, row)| !shown.contains(index) && row.state == State::Queued)
+                .count();
+            let _ = queue!(stdout, terminal::Clear(terminal::ClearType::CurrentLine));
+            let _ = writeln!(
+                stdout,
+                "{}",
+                format!(
+                    "  … {} more: {} done, {} queued",
+                    hidden,
+                    hidden - queued,
+                    queued
+                )
+                .dimmed()
+            );
+        }
+        // The previous frame may have been taller, e.g. before a resize
+        let _ = queue!(stdout, terminal::Clear(terminal::ClearType::FromCursorDown));
+        let _ = stdout.flush();
+        self.drawn
+            .store(shown.len() + usize::from(hidden > 0), Ordering::SeqCst);
+    }
+}
+
+fn outcome_icon(outcome: Outcome) -> ColoredString {
+    match outcome {
+        Outcome::Ok => "✔".green(),
+        Outcome::Failed | Outcome::AuthFailed => "✘".red(),
+        Outcome::TimedOut(_) => "⏱".magenta(),
+        Outcome::Skipped => "-".blue(),
+        Outcome::Cancelled | Outcome::NotRun => "-".yellow(),
+    }
+}
+
+fn outcome_label(outcome: Outcome) -> ColoredString {
+    match outcome {
+        Outcome::Ok => "ok".green(),
+        Outcome::Failed => "failed".red(),
+        Outcome::AuthFailed => "auth failed".red(),
+        Outcome::TimedOut(_) => "timed out".magenta(),
+        Outcome::Cancelled => "cancelled".yellow(),
+        Outcome::Skipped => "skipped".blue(),
+        Outcome::NotRun => "not run".yellow(),
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:.1}s", elapsed.as_secs_f64())
+}
@@ -1,74 +1,539 @@
-use crate::config::{Config, get_repositories_by_names};
+use super::output::{OutputMode, RepoOutput};
+use super::parallel;
+use super::placeholders::Placeholders;
+use super::runs::{RepoLog, RepoReport, RunRecorder};
+use super::summary::{Outcome, Summary};
+use crate::config::{Config, Repository, Selection, select_repositories};
+use crate::git::GitBackend;
+use crate::process::{self, Interrupt, Stream};
 use colored::*;
+use std::path::PathBuf;
 use std::process::Command as ProcessCommand;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
-pub fn run_command(config: &Config, command: &str, names: &[&str]) {
-    let repos = get_repositories_by_names(config, names);
+/// What `gitpower run` executes in each repository.
+#[derive(Debug, Clone)]
+pub enum Invocation {
+    /// A command line interpreted by `shell` (`sh`, `bash`, `zsh` or `fish`)
+    Shell { shell: String, command: String },
+    /// A program and its arguments, run without a shell
+    Argv(Vec<String>),
+}
+
+impl Invocation {
+    fn display(&self) -> String {
+        match self {
+            Invocation::Shell { command, .. } => command.clone(),
+            Invocation::Argv(argv) => argv
+                .iter()
+                .map(|arg| shell_quote(arg))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    /// Substitutes placeholders; values are quoted to match the surrounding
+    /// shell quotes when a shell will parse them.
+    fn expand(&self, placeholders: &Placeholders) -> Result<Invocation, String> {
+        Ok(match self {
+            Invocation::Shell { shell, command } => Invocation::Shell {
+                shell: shell.clone(),
+                command: placeholders.expand(command, quote_in_context)?,
+            },
+            Invocation::Argv(argv) => Invocation::Argv(
+                argv.iter()
+                    .map(|arg| placeholders.expand(arg, |_, value| value.to_string()))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    fn to_command(&self) -> ProcessCommand {
+        match self {
+            Invocation::Shell { shell, command } => {
+                let mut child = ProcessCommand::new(shell);
+                child.arg("-c").arg(command);
+                child
+            }
+            Invocation::Argv(argv) => {
+                let mut child = ProcessCommand::new(&argv[0]);
+                child.args(&argv[1..]);
+                child
+            }
+        }
+    }
+}
+
+/// Quotes `arg` for display so it reads back as a single shell word.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Quotes a placeholder `value` for the shell quoting context `before` leaves
+/// open, so `{path}`, `"{path}"` and `'{path}'` all expand to the value as-is.
+fn quote_in_context(before: &str, value: &str) -> String {
+    #[derive(Clone, Copy)]
+    enum Context {
+        Unquoted,
+        Single,
+        Double,
+    }
+
+    let mut context = Context::Unquoted;
+    let mut chars = before.chars();
+    while let Some(c) = chars.next() {
+        context = match (context, c) {
+            (Context::Unquoted, '\\') | (Context::Double, '\\') => {
+                chars.next();
+                continue;
+            }
+            (Context::Unquoted, '\'') => Context::Single,
+            (Context::Unquoted, '"') => Context::Double,
+            (Context::Single, '\'') | (Context::Double, '"') => Context::Unquoted,
+            (context, _) => context,
+        };
+    }
+
+    match context {
+        Context::Unquoted => shell_quote(value),
+        Context::Single => value.replace('\'', r"'\''"),
+        Context::Double => value
+            .chars()
+            .flat_map(|c| {
+                let escape = matches!(c, '\\' | '"' | '$' | '`').then_some('\\');
+                escape.into_iter().chain([c])
+            })
+            .collect(),
+    }
+}
+
+/// How `gitpower run` executes the command across repositories.
+#[derive(Debug, Clone)]
+pub struct RunSettings {
+    /// Number of repositories to run in at the same time
+    pub jobs: usize,
+    pub output: OutputMode,
+    /// Only print the command each repository would run
+    pub dry_run: bool,
+    /// Precondition that must succeed in a repository for the command to run there
+    pub only_if: Option<Invocation>,
+    /// Stop starting repositories after the first failure
+    pub fail_fast: bool,
+    /// Stop starting repositories after this many failures
+    pub max_failures: Option<usize>,
+    /// Where to write the log files and report, instead of the run's entry in `runs_dir`
+    pub output_dir: Option<PathBuf>,
+    /// Where runs are recorded for `gitpower runs`
+    pub runs_dir: PathBuf,
+}
+
+impl RunSettings {
+    fn failure_limit(&self) -> Option<usize> {
+        if self.fail_fast {
+            Some(1)
+        } else {
+            self.max_failures
+        }
+    }
+}
+
+/// Everything needed to run the command in one repository.
+struct Prepared {
+    path: PathBuf,
+    branch: String,
+    groups: String,
+    invocation: Invocation,
+    condition: Option<Invocation>,
+}
+
+impl Prepared {
+    /// `invocation` set up to run in the repository, telling it which one it runs in.
+    fn command(&self, invocation: &Invocation, repo: &Repository) -> ProcessCommand {
+        let mut child = invocation.to_command();
+        child
+            .current_dir(&self.path)
+            .env("GITPOWER_REPO_NAME", &repo.name)
+            .env("GITPOWER_REPO_PATH", &self.path)
+            .env("GITPOWER_BRANCH", &self.branch)
+            .env("GITPOWER_GROUPS", &self.groups);
+        child
+    }
+}
+
+pub fn run_command(
+    config: &Config,
+    git: &dyn GitBackend,
+    invocation: &Invocation,
+    selection: &Selection,
+    settings: &RunSettings,
+) {
+    let repos = select_repositories(config, selection);
 
     if repos.is_empty() {
         println!("No repositories to run command in.");
         return;
     }
 
+    if settings.dry_run {
+        preview(config, git, invocation, settings, &repos);
+        return;
+    }
+
     println!(
         "{} {}",
         "Running command in repositories:".green().bold(),
-        command.yellow()
+        invocation.display().yellow()
     );
 
-    for repo in repos {
-        println!("\n{} ({})", repo.name.yellow().bold(), repo.path);
+    let recorder = match RunRecorder::create(
+        &settings.runs_dir,
+        settings.output_dir.as_deref(),
+        &invocation.display(),
+    ) {
+        Ok(recorder) => Some(recorder),
+        Err(e) if settings.output_dir.is_some() => {
+            eprintln!(
+                "{} Cannot write to output directory: {}",
+                "ERROR:".red().bold(),
+                e
+            );
+            return;
+        }
+        Err(e) => {
+            eprintln!(
+                "{} This run will not be recorded: {}",
+                "WARNING:".yellow().bold(),
+                e
+            );
+            None
+        }
+    };
+
+    let width = repos
+        .iter()
+        .map(|repo| repo.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let indexed: Vec<(usize, &Repository)> = repos.iter().copied().enumerate().collect();
 
-        let path = shellexpand::tilde(&repo.path);
-        let repo_path = std::path::Path::new(path.as_ref());
+    let failure_limit = settings.failure_limit();
+    let failures = AtomicUsize::new(0);
 
-        if !repo_path.exists() {
-            println!("  {} Repository path does not exist", "ERROR:".red().bold());
-            continue;
+    let outcomes = parallel::map(&indexed, settings.jobs, |(index, repo)| {
+        let stopped = failure_limit.is_some_and(|limit| failures.load(Ordering::SeqCst) >= limit);
+        if process::is_cancelled() || stopped {
+            return (Outcome::NotRun, None);
         }
 
-        // Run the custom command
-        let output = ProcessCommand::new("sh")
-            .current_dir(repo_path)
-            .arg("-c")
-            .arg(command)
-            .output();
+        let started = Instant::now();
+        let log = recorder
+            .as_ref()
+            .and_then(|recorder| match recorder.repo_log(repo) {
+                Ok(log) => Some(log),
+                Err(e) => {
+                    eprintln!(
+                        "{} Output of {} will not be recorded: {}",
+                        "WARNING:".yellow().bold(),
+                        repo.name,
+                        e
+                    );
+                    None
+                }
+            });
+        let output = RepoOutput::new(settings.output, repo, *index, width);
+        let outcome = run_in_repository(
+            config,
+            git,
+            invocation,
+            settings.only_if.as_ref(),
+            repo,
+            &output,
+            log.as_ref(),
+        );
+        output.finish();
 
-        match output {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let stderr = String::from_utf8_lossy(&output.stderr);
+        if outcome.is_failure() {
+            failures.fetch_add(1, Ordering::SeqCst);
+        }
+        let report = log.map(|log| log.report(repo, outcome.name(), started.elapsed()));
+        (outcome, report)
+    });
 
-                if !stdout.is_empty() {
-                    println!("  {}", stdout);
-                }
+    let mut summary = Summary::new();
+    let mut reports = Vec::new();
+    for (repo, (outcome, report)) in repos.iter().zip(outcomes) {
+        summary.record(&repo.name, outcome);
+        reports.push(report.unwrap_or_else(|| RepoReport::without_logs(repo, outcome.name())));
+    }
+    let not_run = reports
+        .iter()
+        .any(|report| report.outcome == Outcome::NotRun.name());
+    if let Some(limit) = failure_limit
+        && failures.load(Ordering::SeqCst) >= limit
+        && not_run
+    {
+        summary.note(format!(
+            "Stopped after {} failure{}, remaining repositories were not run",
+            limit,
+            if limit == 1 { "" } else { "s" }
+        ));
+    }
+    summary.print("Command execution complete!");
 
-                if !stderr.is_empty() {
-                    eprintln!("  {}", stderr);
-                }
+    if let Some(recorder) = recorder {
+        let id = recorder.id().to_string();
+        match recorder.finish(reports) {
+            Ok(dir) => println!(
+                "Logs and report in {} (gitpower runs show {})",
+                dir.display(),
+                id
+            ),
+            Err(e) => eprintln!(
+                "{} Failed to write run report: {}",
+                "WARNING:".yellow().bold(),
+                e
+            ),
+        }
+    }
+}
 
-                if output.status.success() {
-                    println!(
-                        "  {} Command executed successfully",
-                        "SUCCESS:".green().bold()
-                    );
-                } else {
-                    println!(
-                        "  {} Command failed with code {}",
-                        "ERROR:".red().bold(),
-                        output.status.code().unwrap_or(-1)
-                    );
+/// Prints the command each repository would run, with placeholders expanded.
+fn preview(
+    config: &Config,
+    git: &dyn GitBackend,
+    invocation: &Invocation,
+    settings: &RunSettings,
+    repos: &[&Repository],
+) {
+    println!(
+        "{} {}",
+        "Dry run, commands are not executed:".yellow().bold(),
+        invocation.display()
+    );
+
+    for repo in repos {
+        println!("\n{} ({})", repo.name.yellow().bold(), repo.path);
+        match prepare(config, git, invocation, settings.only_if.as_ref(), repo) {
+            Ok(prepared) => {
+                if let Some(condition) = &prepared.condition {
+                    println!("  only if: $ {}", condition.display());
                 }
+                println!("  $ {}", prepared.invocation.display());
+            }
+            Err(e) => println!("  {} {}", "ERROR:".red().bold(), e),
+        }
+    }
+}
+
+/// Resolves the repository's path and expands the command (and precondition) for it.
+fn prepare(
+    config: &Config,
+    git: &dyn GitBackend,
+    invocation: &Invocation,
+    condition: Option<&Invocation>,
+    repo: &Repository,
+) -> Result<Prepared, String> {
+    let path = PathBuf::from(shellexpand::tilde(&repo.path).as_ref());
+    if !path.exists() {
+        return Err("Repository path does not exist".to_string());
+    }
+
+    let branch = git
+        .current_branch(&path)
+        .or_else(|| repo.branch.clone())
+        .unwrap_or_default();
+    let remote_name = repo.remote.as_deref().unwrap_or("origin");
+    let remote = git
+        .remotes(&path)
+        .into_iter()
+        .find(|remote| remote.name == remote_name)
+        .map(|remote| remote.url)
+        .or_else(|| repo.remote.clone())
+        .unwrap_or_default();
+    let groups = config.groups_of(repo);
+
+    let placeholders = Placeholders {
+        name: &repo.name,
+        path: &path.to_string_lossy(),
+        branch: &branch,
+        remote: &remote,
+        groups: &groups,
+        tags: repo.tags.as_deref().unwrap_or_default(),
+    };
+    let invocation = invocation.expand(&placeholders)?;
+    let condition = condition
+        .map(|condition| condition.expand(&placeholders))
+        .transpose()?;
+
+    Ok(Prepared {
+        groups: groups.join(","),
+        path,
+        branch,
+        invocation,
+        condition,
+    })
+}
+
+/// Runs `invocation` in `repo` if `only_if` succeeds there, printing to `output`.
+pub(super) fn run_in_repository(
+    config: &Config,
+    git: &dyn GitBackend,
+    invocation: &Invocation,
+    only_if: Option<&Invocation>,
+    repo: &Repository,
+    output: &RepoOutput,
+    log: Option<&RepoLog>,
+) -> Outcome {
+    let prepared = match prepare(config, git, invocation, only_if, repo) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            output.error_line(&format!("{} {}", "ERROR:".red().bold(), e));
+            return Outcome::Failed;
+        }
+    };
+
+    if let Some(condition) = &prepared.condition {
+        let child = prepared.command(condition, repo);
+        match process::run(child, config.timeout_for(repo)) {
+            Ok(finished) if finished.interrupted.is_some() => {
+                return report_interrupt(output, finished.interrupted.unwrap());
+            }
+            Ok(finished) if finished.status.success() => {}
+            _ => {
+                output.line(&format!(
+                    "{} Precondition failed: {}",
+                    "SKIPPED:".blue().bold(),
+                    condition.display()
+                ));
+                return Outcome::Skipped;
             }
-            Err(e) => {
-                println!(
-                    "  {} Failed to execute command: {}",
+        }
+    }
+
+    // Run the custom command
+    let child = prepared.command(&prepared.invocation, repo);
+
+    let on_line = |stream: Stream, line: &str| match stream {
+        Stream::Stdout => {
+            output.line(line);
+            if let Some(log) = log {
+                log.stdout(line);
+            }
+        }
+        Stream::Stderr => {
+            output.error_line(line);
+            if let Some(log) = log {
+                log.stderr(line);
+            }
+        }
+    };
+
+    match process::run_streaming(child, config.timeout_for(repo), &on_line) {
+        Ok(finished) => {
+            if let Some(log) = log
+                && finished.interrupted.is_none()
+            {
+                log.set_exit_code(finished.status.code());
+            }
+            if let Some(interrupt) = finished.interrupted {
+                report_interrupt(output, interrupt)
+            } else if finished.status.success() {
+                output.line(&format!(
+                    "{} Command executed successfully",
+                    "SUCCESS:".green().bold()
+                ));
+                Outcome::Ok
+            } else {
+                output.line(&format!(
+                    "{} Command failed with code {}",
                     "ERROR:".red().bold(),
-                    e
-                );
+                    finished.status.code().unwrap_or(-1)
+                ));
+                Outcome::Failed
             }
         }
+        Err(e) => {
+            output.error_line(&format!(
+                "{} Failed to execute command: {}",
+                "ERROR:".red().bold(),
+                e
+            ));
+            Outcome::Failed
+        }
+    }
+}
+
+fn report_interrupt(output: &RepoOutput, interrupt: Interrupt) -> Outcome {
+    let outcome = Outcome::interrupted(interrupt);
+    match outcome {
+        Outcome::TimedOut(limit) => output.line(&format!(
+            "{} Timed out after {}s",
+            "TIMEOUT:".magenta().bold(),
+            limit.as_secs()
+        )),
+        _ => output.line(&format!("{} Cancelled", "CANCELLED:".yellow().bold())),
     }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("\n{}", "Command execution complete!".green().bold());
+    fn placeholders() -> Placeholders<'static> {
+        Placeholders {
+            name: "api",
+            path: "/src/it's here",
+            branch: "main",
+            remote: "origin",
+            groups: &[],
+            tags: &[],
+        }
+    }
+
+    #[test]
+    fn quotes_words_the_shell_would_split() {
+        assert_eq!(shell_quote("feature/x-1"), "feature/x-1");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn quotes_values_inside_user_quotes() {
+        assert_eq!(quote_in_context("cd ", "a b"), "'a b'");
+        assert_eq!(quote_in_context("cd \"", "it's $HOME"), r"it's \$HOME");
+        assert_eq!(quote_in_context("cd '", "it's"), r"it'\''s");
+        assert_eq!(quote_in_context("echo \"a\" ", "a b"), "'a b'");
+        assert_eq!(quote_in_context(r#"echo "\"" "#, "a b"), "'a b'");
+        assert_eq!(quote_in_context(r"echo \' ", "a b"), "'a b'");
+        assert_eq!(quote_in_context(r#"echo '\' ""#, "`x`"), r"\`x\`");
+    }
+
+    #[test]
+    fn quotes_values_only_for_the_shell() {
+        let shell = Invocation::Shell {
+            shell: "sh".to_string(),
+            command: "cd {path} && git log {branch}".to_string(),
+        };
+        match shell.expand(&placeholders()).unwrap() {
+            Invocation::Shell { command, .. } => {
+                assert_eq!(command, r"cd '/src/it'\''s here' && git log main")
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let argv = Invocation::Argv(vec!["ls".to_string(), "{path}".to_string()]);
+        match argv.expand(&placeholders()).unwrap() {
+            Invocation::Argv(argv) => assert_eq!(argv, ["ls", "/src/it's here"]),
+            other => panic!("{:?}", other),
+        }
+    }
 }
@@ -0,0 +1,355 @@
+use super::time::{format_id, format_timestamp};
+use crate::config::Repository;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// How many past runs are kept under the runs directory.
+const KEEP_RUNS: usize = 50;
+
+const REPORT_FILE: &str = "report.json";
+
+/// Results of one `gitpower run`, written to `report.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunReport {
+    pub id: String,
+    pub command: String,
+    /// UTC, e.g. `2024-05-01T09:30:00Z`
+    pub started_at: String,
+    pub duration_secs: f64,
+    /// Directory holding the per-repository log files
+    pub output_dir: PathBuf,
+    pub repositories: Vec<RepoReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoReport {
+    pub name: String,
+    pub path: String,
+    pub outcome: String,
+    /// `None` when the command did not run or was killed
+    pub exit_code: Option<i32>,
+    pub duration_secs: f64,
+    /// Log file names relative to `output_dir`, absent when nothing ran
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+impl RepoReport {
+    /// Result of a repository whose output was not captured, e.g. one that never started.
+    pub fn without_logs(repo: &Repository, outcome: &str) -> Self {
+        RepoReport {
+            name: repo.name.clone(),
+            path: repo.path.clone(),
+            outcome: outcome.to_string(),
+            exit_code: None,
+            duration_secs: 0.0,
+            stdout: None,
+            stderr: None,
+        }
+    }
+}
+
+/// Directory past runs are kept in, next to the config file.
+pub fn runs_dir(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("runs")
+}
+
+/// A run being recorded: logs go to `output_dir`, and the report is written
+/// there and to the run's entry under the runs directory.
+pub struct RunRecorder {
+    id: String,
+    command: String,
+    started: SystemTime,
+    output_dir: PathBuf,
+    history_dir: PathBuf,
+    /// Log file stems handed out so far
+    log_stems: Mutex<HashSet<String>>,
+}
+
+impl RunRecorder {
+    /// Creates the run's directories. Logs go to `output_dir`, or into the
+    /// run's entry under `runs_dir` when not given.
+    pub fn create(runs_dir: &Path, output_dir: Option<&Path>, command: &str) -> io::Result<Self> {
+        let started = SystemTime::now();
+        let base = format_id(started);
+
+        fs::create_dir_all(runs_dir)?;
+        // Reports refer to their logs by absolute path, so they can be shown from anywhere
+        let runs_dir = &fs::canonicalize(runs_dir)?;
+        let mut id = base.clone();
+        let mut attempt = 1;
+        let history_dir = loop {
+            let dir = runs_dir.join(&id);
+            match fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    attempt += 1;
+                    id = format!("{}-{}", base, attempt);
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let output_dir = match output_dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                fs::canonicalize(dir)?
+            }
+            None => history_dir.clone(),
+        };
+
+        prune(runs_dir);
+
+        Ok(Self {
+            id,
+            command: command.to_string(),
+            started,
+            output_dir,
+            history_dir,
+            log_stems: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Opens the stdout and stderr log files of `repo`.
+    pub fn repo_log(&self, repo: &Repository) -> io::Result<RepoLog> {
+        let base: String = repo
+            .name
+            .chars()
+            .map(|c| if matches!(c, '/' | '\\') { '_' } else { c })
+            .collect();
+        // Names like `a/b` and `a_b` map to the same base, so number repeats
+        let stem = {
+            let mut stems = self.log_stems.lock().unwrap();
+            let mut stem = base.clone();
+            let mut attempt = 1;
+            while !stems.insert(stem.clone()) {
+                attempt += 1;
+                stem = format!("{}-{}", base, attempt);
+            }
+            stem
+        };
+        let stdout = format!("{}.stdout.log", stem);
+        let stderr = format!("{}.stderr.log", stem);
+
+        Ok(RepoLog {
+            stdout: Mutex::new(BufWriter::new(File::create(self.output_dir.join(&stdout))?)),
+            stderr: Mutex::new(BufWriter::new(File::create(self.output_dir.join(&stderr))?)),
+            files: (stdout, stderr),
+            exit_code: Mutex::new(None),
+        })
+    }
+
+    /// Writes `report.json`, returning where the logs and report are.
+    pub fn finish(self, repositories: Vec<RepoReport>) -> io::Result<PathBuf> {
+        let report = RunReport {
+            id: self.id,
+            command: self.command,
+            started_at: format_timestamp(self.started),
+            duration_secs: self.started.elapsed().unwrap_or_default().as_secs_f64(),
+            output_dir: self.output_dir.clone(),
+            repositories,
+        };
+        let json = serde_json::to_string_pretty(&report)?;
+
+        fs::write(self.output_dir.join(REPORT_FILE), &json)?;
+        if self.history_dir != self.output_dir {
+            fs::write(self.history_dir.join(REPORT_FILE), &json)?;
+        }
+        Ok(self.output_dir)
+    }
+}
+
+/// Log files of one repository, written from the threads reading its child.
+pub struct RepoLog {
+    stdout: Mutex<BufWriter<File>>,
+    stderr: Mutex<BufWriter<File>>,
+    files: (String, String),
+    exit_code: Mutex<Option<i32>>,
+}
+
+impl RepoLog {
+    pub fn stdout(&self, line: &str) {
+        let _ = writeln!(self.stdout.lock().unwrap(), "{}", line);
+    }
+
+    pub fn stderr(&self, line: &str) {
+        let _ = writeln!(self.stderr.lock().unwrap(), "{}", line);
+    }
+
+    pub fn set_exit_code(&self, code: Option<i32>) {
+        *self.exit_code.lock().unwrap() = code;
+    }
+
+    /// Flushes the logs and describes the repository's result.
+    pub fn report(self, repo: &Repository, outcome: &str, duration: Duration) -> RepoReport {
+        let _ = self.stdout.lock().unwrap().flush();
+        let _ = self.stderr.lock().unwrap().flush();
+        RepoReport {
+            name: repo.name.clone(),
+            path: repo.path.clone(),
+            outcome: outcome.to_string(),
+            exit_code: *self.exit_code.lock().unwrap(),
+            duration_secs: duration.as_secs_f64(),
+            stdout: Some(self.files.0),
+            stderr: Some(self.files.1),
+        }
+    }
+}
+
+/// Lists past runs, newest first.
+pub fn list_runs(runs_dir: &Path) {
+    let ids = run_ids(runs_dir);
+    if ids.is_empty() {
+        println!("No recorded runs.");
+        return;
+    }
+
+    println!("{}", "Recorded runs:".green().bold());
+    for id in ids.iter().rev() {
+        match load_report(runs_dir, id) {
+            Ok(report) => {
+                let failed = report
+                    .repositories
+                    .iter()
+                    .filter(|repo| repo.outcome != "ok" && repo.outcome != "skipped")
+                    .count();
+                let status = if failed == 0 {
+                    "ok".green()
+                } else {
+                    format!("{} not ok", failed).red()
+                };
+                println!(
+                    "  {} {} ({} repositories, {})",
+                    report.id.yellow(),
+                    report.command,
+                    report.repositories.len(),
+                    status
+                );
+            }
+            Err(_) => println!("  {} {}", id.yellow(), "(incomplete)".dimmed()),
+        }
+    }
+}
+
+/// Prints a past run's results; `id` may be `latest`. With `logs`, the
+/// captured output of every repository is printed too.
+pub fn show_run(runs_dir: &Path, id: &str, logs: bool) {
+    let id = if id == "latest" {
+        match run_ids(runs_dir).pop() {
+            Some(id) => id,
+            None => {
+                println!("No recorded runs.");
+                return;
+            }
+        }
+    } else {
+        id.to_string()
+    };
+
+    let report = match load_report(runs_dir, &id) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{} Cannot read run '{}': {}", "ERROR:".red().bold(), id, e);
+            return;
+        }
+    };
+
+    println!("{} {}", "Run".green().bold(), report.id.yellow().bold());
+    println!("  Command:  {}", report.command);
+    println!("  Started:  {}", report.started_at);
+    println!("  Duration: {:.1}s", report.duration_secs);
+    println!("  Logs:     {}", report.output_dir.display());
+
+    let width = report
+        .repositories
+        .iter()
+        .map(|repo| repo.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    println!();
+    for repo in &report.repositories {
+        let outcome = match repo.outcome.as_str() {
+            "ok" => repo.outcome.green(),
+            "skipped" => repo.outcome.blue(),
+            "timed out" => repo.outcome.magenta(),
+            "cancelled" | "not run" => repo.outcome.yellow(),
+            _ => repo.outcome.red(),
+        };
+        let exit_code = repo
+            .exit_code
+            .map(|code| format!("exit {}", code))
+            .unwrap_or_default();
+        println!(
+            "  {:width$}  {:11} {:>7.1}s  {}",
+            repo.name.bold(),
+            outcome,
+            repo.duration_secs,
+            exit_code,
+            width = width
+        );
+    }
+
+    if logs {
+        for repo in &report.repositories {
+            println!("\n{} ({})", repo.name.yellow().bold(), repo.path);
+            for file in [&repo.stdout, &repo.stderr].into_iter().flatten() {
+                match fs::read_to_string(report.output_dir.join(file)) {
+                    Ok(content) => {
+                        for line in content.lines() {
+                            println!("  {}", line);
+                        }
+                    }
+                    Err(e) => println!("  {} {}: {}", "WARNING:".yellow().bold(), file, e),
+                }
+            }
+        }
+    }
+}
+
+fn load_report(runs_dir: &Path, id: &str) -> Result<RunReport, Box<dyn Error>> {
+    // Only recorded ids, so `id` cannot point outside the runs directory
+    if !run_ids(runs_dir).iter().any(|known| known == id) {
+        return Err("no such run, see `gitpower runs list`".into());
+    }
+    let content = fs::read_to_string(runs_dir.join(id).join(REPORT_FILE))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Ids of recorded runs, oldest first.
+fn run_ids(runs_dir: &Path) -> Vec<String> {
+    let mut ids: Vec<String> = fs::read_dir(runs_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    // Ids start with the timestamp, so they sort chronologically
+    ids.sort();
+    ids
+}
+
+/// Removes the oldest runs beyond `KEEP_RUNS`.
+fn prune(runs_dir: &Path) {
+    let ids = run_ids(runs_dir);
+    let excess = ids.len().saturating_sub(KEEP_RUNS);
+    for id in &ids[..excess] {
+        let _ = fs::remove_dir_all(runs_dir.join(id));
+    }
+}
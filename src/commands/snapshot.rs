@@ -0,0 +1,472 @@
+use super::branch::{failure, failure_after_clean, for_each_repository, make_clean};
+use super::summary::Outcome;
+use super::sync::{remote_and_branch, remote_name, run_options};
+use super::time::format_timestamp;
+use crate::config::{Config, ConfigFormat, Repository, Selection, select_repositories};
+use crate::git::{GitBackend, with_retries};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// The exact state of a set of repositories, written by `snapshot save`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// UTC, e.g. `2024-05-01T09:30:00Z`
+    pub created_at: String,
+    pub repositories: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub name: String,
+    /// URL of the repository's remote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    /// Checked-out branch; absent when HEAD was detached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    pub sha: String,
+    /// Whether there were uncommitted changes, which the snapshot does not contain
+    pub dirty: bool,
+}
+
+/// Writes the branch and HEAD commit of every selected repository to `file`,
+/// in the format its extension names.
+pub fn snapshot_save(config: &Config, selection: &Selection, git: &dyn GitBackend, file: &Path) {
+    let format = match ConfigFormat::from_path(file) {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("{} {}", "ERROR:".red().bold(), e);
+            return;
+        }
+    };
+
+    let repos = select_repositories(config, selection);
+    let width = repos
+        .iter()
+        .map(|repo| repo.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut entries = Vec::new();
+    for repo in repos {
+        let path = shellexpand::tilde(&repo.path);
+        let path = Path::new(path.as_ref());
+        if !path.exists() {
+            eprintln!(
+                "{} {}: Repository path does not exist, not included",
+                "WARNING:".yellow().bold(),
+                repo.name
+            );
+            continue;
+        }
+        let Some(sha) = git.resolve(path, "HEAD") else {
+            eprintln!(
+                "{} {}: No commit checked out, not included",
+                "WARNING:".yellow().bold(),
+                repo.name
+            );
+            continue;
+        };
+
+        let entry = SnapshotEntry {
+            name: repo.name.clone(),
+            remote: remote_url(git, repo, path),
+            branch: git.current_branch(path),
+            sha,
+            dirty: git.status(path).is_ok_and(|changes| !changes.is_empty()),
+        };
+        println!(
+            "  {:width$}  {} {}{}",
+            repo.name.bold(),
+            short(&entry.sha).yellow(),
+            entry.branch.as_deref().unwrap_or("(detached)"),
+            if entry.dirty {
+                " (uncommitted changes not saved)".red().to_string()
+            } else {
+                String::new()
+            },
+            width = width
+        );
+        entries.push(entry);
+    }
+
+    if entries.is_empty() {
+        println!("No repositories to snapshot.");
+        return;
+    }
+
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION,
+        created_at: format_timestamp(SystemTime::now()),
+        repositories: entries,
+    };
+    let written = format
+        .serialize(&snapshot)
+        .and_then(|content| Ok(fs::write(file, content)?));
+    match written {
+        Ok(()) => println!(
+            "\n{} {} repositories to {}",
+            "Saved".green().bold(),
+            snapshot.repositories.len(),
+            file.display()
+        ),
+        Err(e) => eprintln!(
+            "{} Failed to write {}: {}",
+            "ERROR:".red().bold(),
+            file.display(),
+            e
+        ),
+    }
+}
+
+/// Checks out the commits recorded in `file`: on the recorded branch when its
+/// tip is still that commit, detached otherwise or with `detach`. Commits
+/// missing locally are fetched first.
+pub fn snapshot_restore(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    file: &Path,
+    detach: bool,
+    stash: bool,
+) {
+    let Some(snapshot) = load(file) else {
+        return;
+    };
+    let repos = snapshot_repositories(config, selection, &snapshot);
+
+    println!("{} {}", "Restoring snapshot".green().bold(), file.display());
+    let summary = for_each_repository(&repos, |repo, path| {
+        let Some(entry) = snapshot.repositories.iter().find(|e| e.name == repo.name) else {
+            return (Outcome::Skipped, "not in snapshot".to_string());
+        };
+        let sha = short(&entry.sha);
+
+        // Stay on the branch when it still points at the recorded commit
+        let branch = entry
+            .branch
+            .as_deref()
+            .filter(|branch| !detach && git.resolve(path, branch).as_deref() == Some(&entry.sha));
+        let current = git.current_branch(path);
+        let in_place = git.resolve(path, "HEAD").as_deref() == Some(&entry.sha)
+            && current.as_deref() == branch;
+        if in_place {
+            return (Outcome::Ok, format!("already at {}", sha));
+        }
+
+        if git.resolve(path, &entry.sha).is_none() {
+            let Some(remote) = remote_name(git, repo, path) else {
+                return (
+                    Outcome::Failed,
+                    format!("commit {} not found and no remote to fetch it from", sha),
+                );
+            };
+            let options = run_options(config, repo, None);
+            let output = with_retries(&options, || {
+                git.fetch_commit(path, &remote, &entry.sha, &options)
+            });
+            if !output.success {
+                return failure(&output);
+            }
+            if git.resolve(path, &entry.sha).is_none() {
+                return (Outcome::Failed, format!("commit {} not found", sha));
+            }
+        }
+
+        let stashed = match make_clean(git, path, stash, "snapshot restore") {
+            Ok(stashed) => stashed,
+            Err(result) => return result,
+        };
+
+        let output = git.checkout(path, branch.unwrap_or(&entry.sha));
+        if !output.success {
+            return failure_after_clean(git, path, &stashed, &output);
+        }
+
+        let mut message = match branch {
+            Some(branch) => format!("on {} at {}", branch, sha),
+            None => format!("detached at {}", sha),
+        };
+        // The snapshot has no uncommitted changes, so leave them stashed
+        if let Some(stashed) = stashed {
+            message.push_str(&format!(", {} (`git stash pop` restores them)", stashed));
+        }
+        (Outcome::Ok, message)
+    });
+    summary.print("Restore complete!");
+}
+
+/// Compares the selected repositories with `file`, reporting every difference.
+/// Returns whether they all match.
+pub fn snapshot_verify(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    file: &Path,
+) -> bool {
+    let Some(snapshot) = load(file) else {
+        return false;
+    };
+    let repos = snapshot_repositories(config, selection, &snapshot);
+
+    println!("{} {}", "Verifying snapshot".green().bold(), file.display());
+    let summary = for_each_repository(&repos, |repo, path| {
+        let Some(entry) = snapshot.repositories.iter().find(|e| e.name == repo.name) else {
+            return (Outcome::Skipped, "not in snapshot".to_string());
+        };
+
+        let mut drift = Vec::new();
+        let head = git.resolve(path, "HEAD").unwrap_or_default();
+        if head != entry.sha {
+            drift.push(format!(
+                "at {}, expected {}",
+                short(&head),
+                short(&entry.sha)
+            ));
+        }
+        // A detached HEAD at the recorded commit is what `restore` leaves
+        // behind when the branch moved on, so only another branch is drift
+        if let Some(branch) = git.current_branch(path)
+            && Some(&branch) != entry.branch.as_ref()
+        {
+            drift.push(format!(
+                "on {}, expected {}",
+                branch,
+                entry.branch.as_deref().unwrap_or("(detached)")
+            ));
+        }
+        let remote = remote_url(git, repo, path);
+        if remote != entry.remote {
+            drift.push(format!(
+                "remote is {}, expected {}",
+                remote.as_deref().unwrap_or("(none)"),
+                entry.remote.as_deref().unwrap_or("(none)")
+            ));
+        }
+        if !entry.dirty && git.status(path).is_ok_and(|changes| !changes.is_empty()) {
+            drift.push("has uncommitted changes".to_string());
+        }
+
+        if drift.is_empty() {
+            (Outcome::Ok, "matches".to_string())
+        } else {
+            (Outcome::Failed, drift.join("; "))
+        }
+    });
+    summary.print("Verification complete!");
+    !summary.has_failures()
+}
+
+fn load(file: &Path) -> Option<Snapshot> {
+    let result = (|| -> Result<Snapshot, Box<dyn Error>> {
+        let format = ConfigFormat::from_path(file)?;
+        let snapshot: Snapshot = format.parse(&fs::read_to_string(file)?)?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot version {} is newer than this gitpower supports ({})",
+                snapshot.version, SNAPSHOT_VERSION
+            )
+            .into());
+        }
+        Ok(snapshot)
+    })();
+
+    result
+        .inspect_err(|e| {
+            eprintln!(
+                "{} Cannot read snapshot {}: {}",
+                "ERROR:".red().bold(),
+                file.display(),
+                e
+            )
+        })
+        .ok()
+}
+
+/// Selected repositories, warning about snapshot entries the config does not know.
+fn snapshot_repositories<'a>(
+    config: &'a Config,
+    selection: &Selection,
+    snapshot: &Snapshot,
+) -> Vec<&'a Repository> {
+    for entry in &snapshot.repositories {
+        if !config
+            .repositories
+            .iter()
+            .any(|repo| repo.name == entry.name)
+        {
+            eprintln!(
+                "{} {}: In the snapshot but not in the config, ignored",
+                "WARNING:".yellow().bold(),
+                entry.name
+            );
+        }
+    }
+    select_repositories(config, selection)
+}
+
+/// URL of the repository's remote; the configured `remote` may be a name or a URL.
+fn remote_url(git: &dyn GitBackend, repo: &Repository, path: &Path) -> Option<String> {
+    let (remote, _) = remote_and_branch(repo);
+    git.remotes(path)
+        .into_iter()
+        .find(|r| r.name == remote)
+        .map(|r| r.url)
+        .or_else(|| remote.contains(['/', ':']).then(|| remote.to_string()))
+}
+
+fn short(sha: &str) -> &str {
+    if sha.is_empty() {
+        "(no commit)"
+    } else {
+        &sha[..sha.len().min(7)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fake::FakeRepo;
+    use crate::git::{Commit, StatusEntry};
+    use crate::testing::Fixture;
+    use std::path::PathBuf;
+
+    const SHA: &str = "1111111111111111111111111111111111111111";
+
+    /// A repository at `SHA` on `main`, adjusted by `setup`, and the file
+    /// a snapshot of it was saved to.
+    fn saved(test: &str, setup: impl FnOnce(FakeRepo) -> FakeRepo) -> (Fixture, PathBuf) {
+        let repo = FakeRepo {
+            commits: vec![Commit {
+                sha: SHA.to_string(),
+                summary: "initial".to_string(),
+            }],
+            ..FakeRepo::new("main")
+        };
+        let fixture = Fixture::new(test, [("app", setup(repo))]);
+        let file = fixture.dir.join("snapshot.yml");
+        snapshot_save(&fixture.config, &Selection::default(), &fixture.git, &file);
+        (fixture, file)
+    }
+
+    fn verify(fixture: &Fixture, file: &Path) -> bool {
+        snapshot_verify(&fixture.config, &Selection::default(), &fixture.git, file)
+    }
+
+    #[test]
+    fn save_records_branch_commit_remote_and_dirt() {
+        let (fixture, file) = saved("snapshot-save", |repo| {
+            repo.with_remote("origin", "git@example.com:app.git")
+                .with_change("??", "notes.txt")
+        });
+
+        let snapshot = load(&file).unwrap();
+        assert_eq!(snapshot.repositories.len(), 1);
+        let entry = &snapshot.repositories[0];
+        assert_eq!(entry.sha, SHA);
+        assert_eq!(entry.branch.as_deref(), Some("main"));
+        assert_eq!(entry.remote.as_deref(), Some("git@example.com:app.git"));
+        assert!(entry.dirty);
+        assert!(verify(&fixture, &file));
+    }
+
+    #[test]
+    fn verify_detects_new_commits_and_changes() {
+        let (fixture, file) = saved("snapshot-commit", |repo| repo);
+        fixture
+            .git
+            .add_remote(&fixture.paths[0], "upstream", "git@example.com:app.git");
+        assert!(verify(&fixture, &file), "other remotes are not drift");
+
+        fixture.git.update(&fixture.paths[0], |repo| {
+            repo.changes.push(StatusEntry {
+                code: " M".to_string(),
+                path: "src/main.rs".to_string(),
+            })
+        });
+        assert!(!verify(&fixture, &file), "uncommitted changes");
+
+        fixture.git.commit(&fixture.paths[0], "more");
+        assert!(!verify(&fixture, &file), "moved HEAD");
+    }
+
+    #[test]
+    fn verify_detects_another_branch_but_not_a_detached_head() {
+        let (fixture, file) = saved("snapshot-branch", |repo| repo.with_branch("feature"));
+
+        fixture.git.checkout(&fixture.paths[0], SHA);
+        assert!(verify(&fixture, &file), "detached at the recorded commit");
+
+        fixture.git.checkout(&fixture.paths[0], "feature");
+        assert!(!verify(&fixture, &file), "on another branch");
+    }
+
+    #[test]
+    fn verify_detects_a_changed_remote() {
+        let (mut fixture, file) = saved("snapshot-remote", |repo| repo);
+        fixture.config.repositories[0].remote = Some("origin".to_string());
+        assert!(verify(&fixture, &file));
+
+        fixture
+            .git
+            .add_remote(&fixture.paths[0], "origin", "git@example.com:app.git");
+        assert!(!verify(&fixture, &file));
+    }
+
+    #[test]
+    fn restore_fetches_missing_commits_from_the_configured_remote() {
+        let url = "git@example.com:app.git";
+        let (mut fixture, file) =
+            saved("snapshot-restore", |repo| repo.with_remote("upstream", url));
+        fixture.config.repositories[0].remote = Some(url.to_string());
+
+        // Rewrite the snapshot as if it was taken on another machine
+        let content = fs::read_to_string(&file)
+            .unwrap()
+            .replace(SHA, &"2".repeat(40));
+        fs::write(&file, content).unwrap();
+
+        snapshot_restore(
+            &fixture.config,
+            &Selection::default(),
+            &fixture.git,
+            &file,
+            false,
+            false,
+        );
+
+        let path = fixture.paths[0].display();
+        let calls = fixture.git.calls();
+        assert!(calls.contains(&format!("fetch {} upstream {}", path, "2".repeat(40))));
+        assert!(calls.contains(&format!("checkout {} {}", path, "2".repeat(40))));
+        assert_eq!(fixture.git.repo(&fixture.paths[0]).unwrap().branch, None);
+    }
+
+    #[test]
+    fn restore_without_a_remote_does_not_fetch() {
+        let (fixture, file) = saved("snapshot-no-remote", |repo| repo);
+        let content = fs::read_to_string(&file)
+            .unwrap()
+            .replace(SHA, &"2".repeat(40));
+        fs::write(&file, content).unwrap();
+
+        snapshot_restore(
+            &fixture.config,
+            &Selection::default(),
+            &fixture.git,
+            &file,
+            false,
+            false,
+        );
+
+        let calls = fixture.git.calls();
+        assert!(!calls.iter().any(|call| call.starts_with("fetch ")));
+        assert!(!calls.iter().any(|call| call.starts_with("checkout ")));
+    }
+}
@@ -0,0 +1,238 @@
+use super::branch::{failure, for_each_repository};
+use super::summary::Outcome;
+use super::time::format_id;
+use crate::config::{Config, Selection, select_repositories};
+use crate::git::GitBackend;
+use colored::*;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Stashes made by `gitpower stash save` carry `gitpower[<batch>]: ` before
+/// their message, so a batch can be popped without touching other stashes.
+const BATCH_PREFIX: &str = "gitpower[";
+
+/// Stashes the local changes of every selected repository as one batch.
+pub fn stash_save(config: &Config, selection: &Selection, git: &dyn GitBackend, message: &str) {
+    let repos = select_repositories(config, selection);
+    if repos.is_empty() {
+        println!("No repositories to stash.");
+        return;
+    }
+
+    // Ids have one-second resolution, so number saves within the same second
+    let existing: Vec<String> = config
+        .repositories
+        .iter()
+        .flat_map(|repo| git.stashes(Path::new(shellexpand::tilde(&repo.path).as_ref())))
+        .filter_map(|entry| batch_of(&entry).map(str::to_string))
+        .collect();
+    let base = format_id(SystemTime::now());
+    let mut batch = base.clone();
+    let mut attempt = 1;
+    while existing.contains(&batch) {
+        attempt += 1;
+        batch = format!("{}-{}", base, attempt);
+    }
+    let tagged = format!("{}{}]: {}", BATCH_PREFIX, batch, message);
+    println!(
+        "{} {}",
+        "Stashing changes, batch".green().bold(),
+        batch.yellow()
+    );
+
+    let summary = for_each_repository(&repos, |_, path| {
+        let changes = match git.status(path) {
+            Ok(changes) => changes.len(),
+            Err(e) => return (Outcome::Failed, e),
+        };
+        if changes == 0 {
+            return (Outcome::Skipped, "nothing to stash".to_string());
+        }
+
+        let output = git.stash(path, &tagged);
+        if !output.success {
+            return failure(&output);
+        }
+        let plural = if changes == 1 { "" } else { "s" };
+        (Outcome::Ok, format!("stashed {} change{}", changes, plural))
+    });
+    summary.print("Stash complete!");
+    println!("Restore with: gitpower stash pop --batch {}", batch);
+}
+
+/// Prints the stashes of every selected repository, grouped by repository.
+pub fn stash_list(config: &Config, selection: &Selection, git: &dyn GitBackend) {
+    let mut found = false;
+
+    for repo in select_repositories(config, selection) {
+        let path = shellexpand::tilde(&repo.path);
+        let stashes = git.stashes(Path::new(path.as_ref()));
+        if stashes.is_empty() {
+            continue;
+        }
+        found = true;
+
+        println!("\n{} ({})", repo.name.yellow().bold(), repo.path);
+        for (index, entry) in stashes.iter().enumerate() {
+            let reference = format!("stash@{{{}}}", index);
+            match batch_of(entry) {
+                Some(batch) => println!(
+                    "  {}  {} {}",
+                    reference.dimmed(),
+                    batch.cyan(),
+                    entry.replacen(&format!("{}{}]: ", BATCH_PREFIX, batch), "", 1)
+                ),
+                None => println!("  {}  {}", reference.dimmed(), entry),
+            }
+        }
+    }
+
+    if !found {
+        println!("No stashes.");
+    }
+}
+
+/// Pops the stashes of `batch`, or of the most recent batch, in every
+/// selected repository. Other stashes are left alone.
+pub fn stash_pop(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    batch: Option<&str>,
+) {
+    let repos = select_repositories(config, selection);
+
+    let batch = match batch {
+        Some(batch) => batch.to_string(),
+        None => match repos
+            .iter()
+            .flat_map(|repo| git.stashes(Path::new(shellexpand::tilde(&repo.path).as_ref())))
+            .filter_map(|entry| batch_of(&entry).map(str::to_string))
+            .max_by(|a, b| batch_order(a).cmp(&batch_order(b)))
+        {
+            Some(batch) => batch,
+            None => {
+                println!("No gitpower stashes to pop.");
+                return;
+            }
+        },
+    };
+
+    println!(
+        "{} {}",
+        "Popping stash batch".green().bold(),
+        batch.yellow()
+    );
+    let summary = for_each_repository(&repos, |_, path| {
+        let Some(index) = git
+            .stashes(path)
+            .iter()
+            .position(|entry| batch_of(entry) == Some(batch.as_str()))
+        else {
+            return (Outcome::Skipped, "no stash from this batch".to_string());
+        };
+
+        let output = git.stash_pop(path, index);
+        if !output.success {
+            return failure(&output);
+        }
+        (Outcome::Ok, format!("popped stash@{{{}}}", index))
+    });
+    summary.print("Stash pop complete!");
+}
+
+/// Sort key putting batches in the order they were made: by timestamp, then
+/// by the `-N` suffix of repeated saves within one second.
+fn batch_order(batch: &str) -> (&str, u32) {
+    let timestamp_len = "20240501-093000".len();
+    match batch.split_at_checked(timestamp_len) {
+        Some((timestamp, suffix)) => (
+            timestamp,
+            suffix
+                .strip_prefix('-')
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(1),
+        ),
+        None => (batch, 1),
+    }
+}
+
+/// Batch id of a stash made by `stash save`.
+fn batch_of(entry: &str) -> Option<&str> {
+    let start = entry.find(BATCH_PREFIX)? + BATCH_PREFIX.len();
+    let end = start + entry[start..].find("]: ")?;
+    Some(&entry[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::StatusEntry;
+    use crate::git::fake::FakeRepo;
+    use crate::testing::Fixture;
+
+    #[test]
+    fn batches_sort_by_time_then_number() {
+        let mut batches = [
+            "20240501-093000-10",
+            "20240501-093001",
+            "20240501-093000-2",
+            "20240501-093000",
+        ];
+        batches.sort_by(|a, b| batch_order(a).cmp(&batch_order(b)));
+        assert_eq!(
+            batches,
+            [
+                "20240501-093000",
+                "20240501-093000-2",
+                "20240501-093000-10",
+                "20240501-093001"
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_the_batch_from_the_stash_message() {
+        assert_eq!(
+            batch_of("On main: gitpower[20240501-093000-2]: wip"),
+            Some("20240501-093000-2")
+        );
+        assert_eq!(batch_of("On main: wip"), None);
+    }
+
+    #[test]
+    fn save_and_pop_work_on_one_batch() {
+        let mut repo = FakeRepo::new("main").with_change(" M", "src/lib.rs");
+        repo.stashes.push("On main: by hand".to_string());
+        let fixture = Fixture::new("stash", [("dirty", repo), ("clean", FakeRepo::new("main"))]);
+        let (config, git) = (&fixture.config, &fixture.git);
+        let (dirty, clean) = (&fixture.paths[0], &fixture.paths[1]);
+
+        stash_save(config, &Selection::default(), git, "wip");
+        git.update(dirty, |repo| {
+            repo.changes.push(StatusEntry {
+                code: "??".to_string(),
+                path: "notes.txt".to_string(),
+            })
+        });
+        stash_save(config, &Selection::default(), git, "more");
+
+        let stashes = git.repo(dirty).unwrap().stashes;
+        assert_eq!(stashes.len(), 3);
+        let (first, second) = (
+            batch_of(&stashes[1]).unwrap(),
+            batch_of(&stashes[0]).unwrap(),
+        );
+        assert_ne!(first, second);
+        assert!(git.repo(clean).unwrap().stashes.is_empty());
+
+        // The most recent batch goes first and stashes made by hand stay
+        stash_pop(config, &Selection::default(), git, None);
+        assert_eq!(git.repo(dirty).unwrap().stashes.len(), 2);
+        assert_eq!(batch_of(&git.repo(dirty).unwrap().stashes[0]), Some(first));
+        stash_pop(config, &Selection::default(), git, None);
+        assert_eq!(git.repo(dirty).unwrap().stashes, ["On main: by hand"]);
+        stash_pop(config, &Selection::default(), git, None);
+        assert_eq!(git.repo(dirty).unwrap().stashes.len(), 1);
+    }
+}
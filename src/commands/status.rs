@@ -1,9 +1,9 @@
-use crate::config::{Config, get_repositories_by_names};
-use crate::git::run_git_command;
+use crate::config::{Config, Selection, select_repositories};
+use crate::git::{AheadBehind, GitBackend};
 use colored::*;
 
-pub fn show_repository_status(config: &Config, names: &[&str]) {
-    let repos = get_repositories_by_names(config, names);
+pub fn show_repository_status(config: &Config, selection: &Selection, git: &dyn GitBackend) {
+    let repos = select_repositories(config, selection);
 
     if repos.is_empty() {
         println!("No repositories to check status.");
@@ -24,90 +24,103 @@ pub fn show_repository_status(config: &Config, names: &[&str]) {
         }
 
         // Get current branch
-        let branch_output = run_git_command(repo_path, &["branch", "--show-current"]);
-        let current_branch = String::from_utf8_lossy(&branch_output.stdout)
-            .trim()
-            .to_string();
+        let current_branch = git.current_branch(repo_path).unwrap_or_default();
         println!("  Current branch: {}", current_branch.cyan());
 
         // Get status
-        let status_output = run_git_command(repo_path, &["status", "--porcelain"]);
-        if !status_output.status.success() {
-            println!(
-                "  {} Failed to get repository status",
-                "ERROR:".red().bold()
-            );
-            continue;
-        }
+        let changes = match git.status(repo_path) {
+            Ok(changes) => changes,
+            Err(_) => {
+                println!(
+                    "  {} Failed to get repository status",
+                    "ERROR:".red().bold()
+                );
+                continue;
+            }
+        };
 
-        let status_text = String::from_utf8_lossy(&status_output.stdout);
-        if status_text.trim().is_empty() {
+        if changes.is_empty() {
             println!("  Status: {}", "Clean".green());
         } else {
             println!("  Status: {}", "Changes detected".yellow());
 
-            // Parse status output for better display
-            for line in status_text.lines() {
-                if !line.is_empty() {
-                    let status_code = &line[0..2];
-                    let file_name = &line[3..];
-
-                    let status_desc = match status_code.trim() {
-                        "M" => "Modified:".yellow(),
-                        "A" => "Added:".green(),
-                        "D" => "Deleted:".red(),
-                        "R" => "Renamed:".blue(),
-                        "C" => "Copied:".cyan(),
-                        "U" => "Updated but unmerged:".red(),
-                        "??" => "Untracked:".bright_black(),
-                        _ => "Changed:".normal(),
-                    };
-
-                    println!("    {} {}", status_desc, file_name);
-                }
+            for change in &changes {
+                let status_desc = match change.code.trim() {
+                    "M" => "Modified:".yellow(),
+                    "A" => "Added:".green(),
+                    "D" => "Deleted:".red(),
+                    "R" => "Renamed:".blue(),
+                    "C" => "Copied:".cyan(),
+                    "U" => "Updated but unmerged:".red(),
+                    "??" => "Untracked:".bright_black(),
+                    _ => "Changed:".normal(),
+                };
+
+                println!("    {} {}", status_desc, change.path);
             }
         }
 
         // Get remote status
-        let ahead_behind = run_git_command(
-            repo_path,
-            &["rev-list", "--count", "--left-right", "@{upstream}...HEAD"],
-        );
-        if ahead_behind.status.success() {
-            let output = String::from_utf8_lossy(&ahead_behind.stdout)
-                .trim()
-                .to_string();
-            if output.contains("\t") {
-                let counts: Vec<&str> = output.split('\t').collect();
-                if counts.len() == 2 {
-                    let behind = counts[0];
-                    let ahead = counts[1];
-
-                    if ahead != "0" {
-                        println!("  {} {} commit(s) ahead of remote", "↑".green(), ahead);
-                    }
-
-                    if behind != "0" {
-                        println!("  {} {} commit(s) behind remote", "↓".red(), behind);
-                    }
-
-                    if ahead == "0" && behind == "0" {
-                        println!("  {} In sync with remote", "=".green());
-                    }
+        match git.ahead_behind(repo_path) {
+            Some(AheadBehind { ahead, behind }) => {
+                if ahead != 0 {
+                    println!("  {} {} commit(s) ahead of remote", "↑".green(), ahead);
+                }
+
+                if behind != 0 {
+                    println!("  {} {} commit(s) behind remote", "↓".red(), behind);
+                }
+
+                if ahead == 0 && behind == 0 {
+                    println!("  {} In sync with remote", "=".green());
                 }
             }
-        } else {
-            // Check if remote exists
-            let remote_output = run_git_command(repo_path, &["remote"]);
-            if remote_output.status.success()
-                && !String::from_utf8_lossy(&remote_output.stdout)
-                    .trim()
-                    .is_empty()
-            {
-                println!("  {} No upstream branch set", "!".yellow());
-            } else {
-                println!("  {} No remote configured", "!".yellow());
+            None => {
+                // Check if remote exists
+                if git.remotes(repo_path).is_empty() {
+                    println!("  {} No remote configured", "!".yellow());
+                } else {
+                    println!("  {} No upstream branch set", "!".yellow());
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fake::FakeRepo;
+    use crate::testing::Fixture;
+
+    #[test]
+    fn status_only_reads_and_checks_remotes_without_upstream() {
+        let fixture = Fixture::new(
+            "status",
+            [
+                (
+                    "tracked",
+                    FakeRepo::new("main")
+                        .with_change(" M", "README.md")
+                        .with_remote("origin", "git@example.com:tracked.git")
+                        .with_upstream(1, 0),
+                ),
+                ("local", FakeRepo::new("main")),
+            ],
+        );
+        let (git, tracked, local) = (&fixture.git, &fixture.paths[0], &fixture.paths[1]);
+
+        show_repository_status(&fixture.config, &Selection::default(), git);
+
+        let ops = |path: &std::path::Path| -> Vec<String> {
+            let suffix = format!(" {}", path.display());
+            git.calls()
+                .iter()
+                .filter_map(|call| call.strip_suffix(&suffix).map(str::to_string))
+                .collect()
+        };
+        assert_eq!(ops(tracked), ["branch", "status", "rev-list"]);
+        assert_eq!(ops(local), ["branch", "status", "rev-list", "remote"]);
+        assert_eq!(git.repo(tracked).unwrap().changes.len(), 1);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::git::GitOutput;
+use crate::process::{self, Interrupt};
+use colored::*;
+use std::time::Duration;
+
+/// How a repository fared in a batch command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    Failed,
+    /// Failed because credentials were rejected or would have needed a prompt
+    AuthFailed,
+    TimedOut(Duration),
+    /// Killed by Ctrl-C while running
+    Cancelled,
+    /// Left out on purpose, e.g. because an `--only-if` precondition failed
+    Skipped,
+    /// Never started because the run was cancelled or stopped first
+    NotRun,
+}
+
+impl Outcome {
+    pub fn of(output: &GitOutput) -> Self {
+        match output.interrupted {
+            Some(interrupt) => Self::interrupted(interrupt),
+            None if output.success => Outcome::Ok,
+            None if output.is_auth_failure() => Outcome::AuthFailed,
+            None => Outcome::Failed,
+        }
+    }
+
+    pub fn interrupted(interrupt: Interrupt) -> Self {
+        match interrupt {
+            Interrupt::TimedOut(limit) => Outcome::TimedOut(limit),
+            Interrupt::Cancelled => Outcome::Cancelled,
+        }
+    }
+
+    /// Lowercase label used in reports, e.g. "timed out".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Failed => "failed",
+            Outcome::AuthFailed => "auth failed",
+            Outcome::TimedOut(_) => "timed out",
+            Outcome::Cancelled => "cancelled",
+            Outcome::Skipped => "skipped",
+            Outcome::NotRun => "not run",
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        *self == Outcome::Ok
+    }
+
+    /// Whether this counts towards `--fail-fast` and `--max-failures`.
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Outcome::Failed | Outcome::AuthFailed | Outcome::TimedOut(_)
+        )
+    }
+}
+
+/// Collects per-repository outcomes and prints the totals at the end of a run.
+#[derive(Debug, Default)]
+pub struct Summary {
+    results: Vec<(String, Outcome)>,
+    notes: Vec<String>,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, name: &str, outcome: Outcome) {
+        self.results.push((name.to_string(), outcome));
+    }
+
+    /// Whether any repository failed, timed out or was rejected.
+    pub fn has_failures(&self) -> bool {
+        self.results.iter().any(|(_, outcome)| outcome.is_failure())
+    }
+
+    /// Adds a line explaining the run as a whole, e.g. why it stopped early.
+    pub fn note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
+    }
+
+    /// Prints totals, the repositories that did not succeed, and `done` unless
+    /// the run was cancelled.
+    pub fn print(&self, done: &str) {
+        let count =
+            |matches: fn(&Outcome) -> bool| self.results.iter().filter(|(_, o)| matches(o)).count();
+
+        let mut totals = vec![format!("{} ok", count(|o| o.is_ok()).to_string().green())];
+        let failed = count(|o| *o == Outcome::Failed);
+        if failed > 0 {
+            totals.push(format!("{} failed", failed.to_string().red()));
+        }
+        let auth_failed = count(|o| *o == Outcome::AuthFailed);
+        if auth_failed > 0 {
+            totals.push(format!("{} auth failed", auth_failed.to_string().red()));
+        }
+        let timed_out = count(|o| matches!(o, Outcome::TimedOut(_)));
+        if timed_out > 0 {
+            totals.push(format!("{} timed out", timed_out.to_string().magenta()));
+        }
+        let cancelled = count(|o| *o == Outcome::Cancelled);
+        if cancelled > 0 {
+            totals.push(format!("{} cancelled", cancelled.to_string().yellow()));
+        }
+        let skipped = count(|o| *o == Outcome::Skipped);
+        if skipped > 0 {
+            totals.push(format!("{} skipped", skipped.to_string().blue()));
+        }
+        let not_run = count(|o| *o == Outcome::NotRun);
+        if not_run > 0 {
+            totals.push(format!("{} not run", not_run.to_string().yellow()));
+        }
+
+        println!("\n{} {}", "Summary:".bold(), totals.join(", "));
+
+        for (name, outcome) in &self.results {
+            let label = match outcome {
+                Outcome::Ok => continue,
+                Outcome::Failed => "failed".red().to_string(),
+                Outcome::AuthFailed => "authentication failed".red().to_string(),
+                Outcome::TimedOut(limit) => format!("timed out after {}s", limit.as_secs())
+                    .magenta()
+                    .to_string(),
+                Outcome::Cancelled => "cancelled".yellow().to_string(),
+                Outcome::Skipped => "skipped".blue().to_string(),
+                Outcome::NotRun => "not run".yellow().to_string(),
+            };
+            println!("  {} {}", name.bold(), label);
+        }
+
+        for note in &self.notes {
+            println!("{} {}", "Note:".yellow(), note);
+        }
+
+        if process::is_cancelled() {
+            println!("\n{}", "Interrupted before completion.".yellow().bold());
+        } else {
+            println!("\n{}", done.green().bold());
+        }
+    }
+}
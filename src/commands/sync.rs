@@ -1,9 +1,20 @@
-use crate::config::{Config, get_repositories_by_names};
-use crate::git::{run_git_command, run_git_command_with_output};
+use super::hooks::{run_hooks, run_hooks_quietly};
+use super::parallel;
+use super::progress::Progress;
+use super::summary::{Outcome, Summary};
+use crate::config::{Config, Hook, Repository, Selection, select_repositories};
+use crate::git::{GitBackend, GitOutput, RunOptions, print_output, with_retries};
+use crate::process;
 use colored::*;
+use std::path::Path;
 
-pub fn sync_repositories(config: &Config, names: &[&str]) {
-    let repos = get_repositories_by_names(config, names);
+pub fn sync_repositories(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    retries: Option<u32>,
+) {
+    let repos = select_repositories(config, selection);
 
     if repos.is_empty() {
         println!("No repositories to sync.");
@@ -12,7 +23,14 @@ pub fn sync_repositories(config: &Config, names: &[&str]) {
 
     println!("{}", "Syncing repositories...".green().bold());
 
+    let mut summary = Summary::new();
+
     for repo in repos {
+        if process::is_cancelled() {
+            summary.record(&repo.name, Outcome::NotRun);
+            continue;
+        }
+
         println!("\n{} ({})", repo.name.yellow().bold(), repo.path);
 
         let path = shellexpand::tilde(&repo.path);
@@ -20,52 +38,113 @@ pub fn sync_repositories(config: &Config, names: &[&str]) {
 
         if !repo_path.exists() {
             println!("  {} Repository path does not exist", "ERROR:".red().bold());
+            summary.record(&repo.name, Outcome::Failed);
             continue;
         }
 
-        // Get current status
-        let status_output = run_git_command(repo_path, &["status", "--porcelain"]);
-        if !status_output.status.success() {
+        let options = run_options(config, repo, retries);
+        summary.record(
+            &repo.name,
+            sync_repository(config, git, repo, repo_path, &options),
+        );
+    }
+
+    summary.print("Sync complete!");
+}
+
+/// Commits local changes, pulls, and pushes the new commit if there was one,
+/// running the sync, pull and push hooks around those steps. Returns the
+/// outcome of the first step that did not succeed.
+pub fn sync_repository(
+    config: &Config,
+    git: &dyn GitBackend,
+    repo: &Repository,
+    repo_path: &Path,
+    options: &RunOptions,
+) -> Outcome {
+    let hooks = |hook: Hook| {
+        run_hooks(config, repo, repo_path, hook, &|line| {
+            println!("  {}", line)
+        })
+    };
+
+    let outcome = hooks(Hook::PreSync);
+    if !outcome.is_ok() {
+        return outcome;
+    }
+
+    // Get current status
+    let has_changes = match git.status(repo_path) {
+        Ok(changes) => !changes.is_empty(),
+        Err(_) => {
             println!(
                 "  {} Failed to get repository status",
                 "ERROR:".red().bold()
             );
-            continue;
+            return Outcome::Failed;
         }
+    };
 
-        let has_changes = !String::from_utf8_lossy(&status_output.stdout)
-            .trim()
-            .is_empty();
-
-        if has_changes {
-            println!("  {} Local changes detected", "WARNING:".yellow().bold());
-            // Add all changes
-            run_git_command_with_output(repo_path, &["add", "."]);
-            // Commit changes
-            run_git_command_with_output(
-                repo_path,
-                &["commit", "-m", "Automatic commit from GitPower"],
-            );
+    if has_changes {
+        println!("  {} Local changes detected", "WARNING:".yellow().bold());
+        // Add all changes
+        let output = git.add_all(repo_path);
+        if !print_output(&output) {
+            return Outcome::of(&output);
+        }
+        // Commit changes
+        let output = git.commit(repo_path, "Automatic commit from GitPower");
+        if !print_output(&output) {
+            return Outcome::of(&output);
         }
+    }
+
+    // Pull changes
+    let (remote, branch) = remote_and_branch(repo);
 
-        // Pull changes
-        let branch = repo.branch.as_deref().unwrap_or("main");
-        let remote = repo.remote.as_deref().unwrap_or("origin");
+    let outcome = hooks(Hook::PrePull);
+    if !outcome.is_ok() {
+        return outcome;
+    }
 
-        println!("  Pulling from {}/{}...", remote, branch);
+    println!("  Pulling from {}/{}...", remote, branch);
+    let output = with_retries(options, || git.pull(repo_path, remote, branch, options));
+    if !print_output(&output) {
+        println!("  {} Pull failed", "ERROR:".red().bold());
+        return Outcome::of(&output);
+    }
+
+    let outcome = hooks(Hook::PostPull);
+    if !outcome.is_ok() {
+        return outcome;
+    }
+
+    if has_changes {
+        let outcome = hooks(Hook::PrePush);
+        if !outcome.is_ok() {
+            return outcome;
+        }
 
-        if has_changes {
-            // Push changes
-            println!("  Pushing to {}/{}...", remote, branch);
-            run_git_command_with_output(repo_path, &["push", remote, branch]);
+        // Push changes
+        println!("  Pushing to {}/{}...", remote, branch);
+        let output = with_retries(options, || git.push(repo_path, remote, branch, options));
+        if !print_output(&output) {
+            println!("  {} Push failed", "ERROR:".red().bold());
+            return Outcome::of(&output);
         }
     }
 
-    println!("\n{}", "Sync complete!".green().bold());
+    hooks(Hook::PostSync)
 }
 
-pub fn pull_repositories(config: &Config, names: &[&str]) {
-    let repos = get_repositories_by_names(config, names);
+pub fn pull_repositories(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    retries: Option<u32>,
+    jobs: usize,
+) {
+    let repos = select_repositories(config, selection);
 
     if repos.is_empty() {
         println!("No repositories to pull.");
@@ -74,30 +153,318 @@ pub fn pull_repositories(config: &Config, names: &[&str]) {
 
     println!("{}", "Pulling repositories...".green().bold());
 
-    for repo in repos {
-        println!("\n{} ({})", repo.name.yellow().bold(), repo.path);
+    let outputs = run_with_progress(
+        config,
+        &repos,
+        retries,
+        jobs,
+        false,
+        |repo, repo_path, options| {
+            if let Err(output) = run_hooks_quietly(config, repo, repo_path, Hook::PrePull) {
+                return output;
+            }
+            let (remote, branch) = remote_and_branch(repo);
+            let output = with_retries(options, || git.pull(repo_path, remote, branch, options));
+            if !output.success {
+                return output;
+            }
+            run_hooks_quietly(config, repo, repo_path, Hook::PostPull)
+                .map_or_else(|e| e, |()| output)
+        },
+    );
 
-        let path = shellexpand::tilde(&repo.path);
-        let repo_path = std::path::Path::new(path.as_ref());
+    report(&repos, outputs, "Pull complete!");
+}
 
-        if !repo_path.exists() {
-            println!("  {} Repository path does not exist", "ERROR:".red().bold());
+pub fn fetch_repositories(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    retries: Option<u32>,
+    jobs: usize,
+) {
+    let repos = select_repositories(config, selection);
+
+    if repos.is_empty() {
+        println!("No repositories to fetch.");
+        return;
+    }
+
+    println!("{}", "Fetching repositories...".green().bold());
+
+    let outputs = run_with_progress(
+        config,
+        &repos,
+        retries,
+        jobs,
+        false,
+        |repo, repo_path, options| {
+            let (remote, _) = remote_and_branch(repo);
+            with_retries(options, || git.fetch(repo_path, remote, options))
+        },
+    );
+
+    report(&repos, outputs, "Fetch complete!");
+}
+
+pub fn clone_repositories(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    retries: Option<u32>,
+    jobs: usize,
+) {
+    let repos = select_repositories(config, selection);
+
+    if repos.is_empty() {
+        println!("No repositories to clone.");
+        return;
+    }
+
+    let (present, missing): (Vec<&Repository>, Vec<&Repository>) = repos
+        .into_iter()
+        .partition(|repo| Path::new(shellexpand::tilde(&repo.path).as_ref()).exists());
+
+    if !present.is_empty() {
+        let names: Vec<&str> = present.iter().map(|repo| repo.name.as_str()).collect();
+        println!("Already present: {}", names.join(", ").dimmed());
+    }
+    if missing.is_empty() {
+        println!("{}", "Nothing to clone.".green().bold());
+        return;
+    }
+
+    println!("{}", "Cloning repositories...".green().bold());
+
+    let outputs = run_with_progress(
+        config,
+        &missing,
+        retries,
+        jobs,
+        true,
+        |repo, repo_path, options| {
+            // `remote` may also name a remote of an existing clone, which is no use here
+            let Some(url) = repo
+                .remote
+                .as_deref()
+                .filter(|remote| remote.contains(['/', ':']))
+            else {
+                return GitOutput::failed("No remote URL configured to clone from");
+            };
+            let output = with_retries(options, || git.clone_repo(url, repo_path, options));
+            if !output.success {
+                return output;
+            }
+            run_hooks_quietly(config, repo, repo_path, Hook::PostClone)
+                .map_or_else(|e| e, |()| output)
+        },
+    );
+
+    report(&missing, outputs, "Clone complete!");
+}
+
+/// Runs `operation` in every repository on up to `jobs` threads behind a
+/// progress display. Unless `cloning`, repositories whose path does not exist
+/// fail without running it. Repositories that never ran have no output.
+fn run_with_progress(
+    config: &Config,
+    repos: &[&Repository],
+    retries: Option<u32>,
+    jobs: usize,
+    cloning: bool,
+    operation: impl Fn(&Repository, &Path, &RunOptions) -> GitOutput + Sync,
+) -> Vec<Option<GitOutput>> {
+    let progress = Progress::new(repos);
+    let indexed: Vec<(usize, &Repository)> = repos.iter().copied().enumerate().collect();
+
+    progress.show(|| {
+        parallel::map(&indexed, jobs, |(index, repo)| {
+            if process::is_cancelled() {
+                progress.finish(*index, Outcome::NotRun);
+                return None;
+            }
+            progress.start(*index);
+
+            let path = shellexpand::tilde(&repo.path);
+            let repo_path = Path::new(path.as_ref());
+
+            let output = if cloning || repo_path.exists() {
+                let mut options = run_options(config, repo, retries);
+                options.progress = progress.reporter(*index);
+                operation(repo, repo_path, &options)
+            } else {
+                GitOutput::failed("Repository path does not exist")
+            };
+
+            progress.finish(*index, Outcome::of(&output));
+            Some(output)
+        })
+    })
+}
+
+/// Prints the output of every repository that did not succeed, then the summary.
+fn report(repos: &[&Repository], outputs: Vec<Option<GitOutput>>, done: &str) {
+    let mut summary = Summary::new();
+
+    for (repo, output) in repos.iter().zip(outputs) {
+        let Some(output) = output else {
+            summary.record(&repo.name, Outcome::NotRun);
             continue;
+        };
+
+        let outcome = Outcome::of(&output);
+        if !outcome.is_ok() {
+            println!("\n{} ({})", repo.name.yellow().bold(), repo.path);
+            print_output(&output);
         }
+        summary.record(&repo.name, outcome);
+    }
 
-        // Pull changes
-        let branch = repo.branch.as_deref().unwrap_or("main");
-        let remote = repo.remote.as_deref().unwrap_or("origin");
+    summary.print(done);
+}
 
-        println!("  Pulling from {}/{}...", remote, branch);
+/// Remote and branch to sync with, defaulting to `origin/main`.
+pub(super) fn remote_and_branch(repo: &Repository) -> (&str, &str) {
+    (
+        repo.remote.as_deref().unwrap_or("origin"),
+        repo.branch.as_deref().unwrap_or("main"),
+    )
+}
 
-        // Actually perform the pull
-        if run_git_command_with_output(repo_path, &["pull", remote, branch]) {
-            println!("  {} Pull successful", "SUCCESS:".green().bold());
-        } else {
-            println!("  {} Pull failed", "ERROR:".red().bold());
-        }
+/// Name of the repository's remote; the configured `remote` may be a name or
+/// the URL of one of the remotes.
+pub(super) fn remote_name(git: &dyn GitBackend, repo: &Repository, path: &Path) -> Option<String> {
+    let (remote, _) = remote_and_branch(repo);
+    git.remotes(path)
+        .into_iter()
+        .find(|r| r.name == remote || r.url == remote)
+        .map(|r| r.name)
+}
+
+pub(super) fn run_options(config: &Config, repo: &Repository, retries: Option<u32>) -> RunOptions {
+    RunOptions {
+        timeout: config.timeout_for(repo),
+        ssh_command: config.ssh_command_for(repo).map(str::to_string),
+        retries: config.retries(retries),
+        progress: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fake::FakeRepo;
+    use crate::testing::Fixture;
+
+    /// Syncs the only repository of `fixture`.
+    fn sync(fixture: &Fixture) -> Outcome {
+        sync_repository(
+            &fixture.config,
+            &fixture.git,
+            &fixture.config.repositories[0],
+            &fixture.paths[0],
+            &RunOptions::default(),
+        )
+    }
+
+    #[test]
+    fn sync_commits_pulls_and_pushes_local_changes() {
+        let fixture = Fixture::new(
+            "sync-changes",
+            [(
+                "app",
+                FakeRepo::new("main")
+                    .with_change(" M", "src/lib.rs")
+                    .with_upstream(0, 2),
+            )],
+        );
+
+        assert_eq!(sync(&fixture), Outcome::Ok);
+        let git = &fixture.git;
+        let state = git.repo(&fixture.paths[0]).unwrap();
+        assert!(state.changes.is_empty());
+        assert_eq!(state.commits[0].summary, "Automatic commit from GitPower");
+        let upstream = state.upstream.unwrap();
+        assert_eq!((upstream.ahead, upstream.behind), (0, 0));
+        let path = fixture.paths[0].display();
+        assert!(git.calls().contains(&format!("pull {} origin main", path)));
+        assert!(git.calls().contains(&format!("push {} origin main", path)));
+    }
+
+    #[test]
+    fn sync_without_changes_only_pulls() {
+        let fixture = Fixture::new(
+            "sync-clean",
+            [("app", FakeRepo::new("main").with_upstream(0, 1))],
+        );
+
+        assert_eq!(sync(&fixture), Outcome::Ok);
+        let calls = fixture.git.calls();
+        assert!(calls.iter().any(|call| call.starts_with("pull ")));
+        assert!(
+            !calls
+                .iter()
+                .any(|call| call.starts_with("commit ") || call.starts_with("push "))
+        );
     }
 
-    println!("\n{}", "Pull complete!".green().bold());
+    #[test]
+    fn sync_stops_when_the_pull_fails() {
+        let fixture = Fixture::new(
+            "sync-pull-fails",
+            [(
+                "app",
+                FakeRepo::new("main")
+                    .with_change("??", "notes.txt")
+                    .failing("pull", "CONFLICT (content): Merge conflict in notes.txt"),
+            )],
+        );
+
+        assert_eq!(sync(&fixture), Outcome::Failed);
+        assert!(
+            !fixture
+                .git
+                .calls()
+                .iter()
+                .any(|call| call.starts_with("push "))
+        );
+    }
+
+    #[test]
+    fn pull_uses_the_configured_remote_and_branch() {
+        let mut fixture = Fixture::new(
+            "pull",
+            [
+                ("first", FakeRepo::new("main").with_upstream(0, 3)),
+                ("second", FakeRepo::new("develop")),
+            ],
+        );
+        let other = &mut fixture.config.repositories[1];
+        other.remote = Some("upstream".to_string());
+        other.branch = Some("develop".to_string());
+        let (git, first, second) = (&fixture.git, &fixture.paths[0], &fixture.paths[1]);
+
+        pull_repositories(&fixture.config, &Selection::default(), git, None, 2);
+
+        assert_eq!(git.repo(first).unwrap().upstream.unwrap().behind, 0);
+        let calls = git.calls();
+        assert!(calls.contains(&format!("pull {} origin main", first.display())));
+        assert!(calls.contains(&format!("pull {} upstream develop", second.display())));
+    }
+
+    #[test]
+    fn pull_skips_missing_paths() {
+        let fixture = Fixture::new("pull-missing", [("missing", FakeRepo::new("main"))]);
+        std::fs::remove_dir(&fixture.paths[0]).unwrap();
+
+        pull_repositories(
+            &fixture.config,
+            &Selection::default(),
+            &fixture.git,
+            None,
+            1,
+        );
+
+        assert!(fixture.git.calls().is_empty());
+    }
 }
@@ -0,0 +1,270 @@
+use super::branch::{failure, for_each_repository, print_matrix};
+use super::summary::Outcome;
+use super::sync::{remote_and_branch, run_options};
+use crate::config::{Config, Selection, select_repositories};
+use crate::git::{GitBackend, with_retries};
+use colored::*;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// How `gitpower tag` creates its tags.
+#[derive(Debug, Clone, Default)]
+pub struct TagSettings {
+    /// Tag message; the tag name when not given
+    pub message: Option<String>,
+    /// Create GPG-signed tags instead of plain annotated ones
+    pub sign: bool,
+    /// Commit to tag; `HEAD` when not given
+    pub target: Option<String>,
+    /// Push the tag to each repository's remote afterwards
+    pub push: bool,
+    pub retries: Option<u32>,
+}
+
+/// Creates the same annotated tag in every selected repository. Nothing is
+/// tagged unless the tag is new everywhere and the target resolves everywhere.
+pub fn create_tag(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    tag: &str,
+    settings: &TagSettings,
+) {
+    let repos = select_repositories(config, selection);
+    if repos.is_empty() {
+        println!("No repositories to tag.");
+        return;
+    }
+
+    let target = settings.target.as_deref().unwrap_or("HEAD");
+    let mut problems = Vec::new();
+    for repo in &repos {
+        let path = shellexpand::tilde(&repo.path);
+        let path = Path::new(path.as_ref());
+        let problem = if !path.exists() {
+            "repository path does not exist".to_string()
+        } else if git.tags(path).iter().any(|t| t == tag) {
+            format!("already has tag '{}'", tag)
+        } else if git.resolve(path, target).is_none() {
+            format!("'{}' does not resolve to a commit", target)
+        } else {
+            continue;
+        };
+        problems.push((&repo.name, problem));
+    }
+    if !problems.is_empty() {
+        eprintln!("{} Not tagging any repository:", "ERROR:".red().bold());
+        for (name, problem) in problems {
+            eprintln!("  {} {}", name.bold(), problem);
+        }
+        return;
+    }
+
+    println!("{} {}", "Tagging:".green().bold(), tag.yellow());
+    let message = settings.message.as_deref().unwrap_or(tag);
+    let summary = for_each_repository(&repos, |repo, path| {
+        let output = git.create_tag(path, tag, target, message, settings.sign);
+        if !output.success {
+            return failure(&output);
+        }
+
+        let sha = git.resolve(path, tag).unwrap_or_default();
+        let tagged = format!("tagged {}", &sha[..sha.len().min(7)]);
+        if !settings.push {
+            return (Outcome::Ok, tagged);
+        }
+
+        let (remote, _) = remote_and_branch(repo);
+        let options = run_options(config, repo, settings.retries);
+        let output = with_retries(&options, || {
+            git.push_tag(path, remote, tag, false, &options)
+        });
+        if !output.success {
+            let (outcome, error) = failure(&output);
+            return (outcome, format!("{}, push failed: {}", tagged, error));
+        }
+        (Outcome::Ok, format!("{}, pushed to {}", tagged, remote))
+    });
+    summary.print("Tagging complete!");
+}
+
+/// Deletes `tag` from every selected repository, and from their remotes with
+/// `remote`.
+pub fn delete_tag(
+    config: &Config,
+    selection: &Selection,
+    git: &dyn GitBackend,
+    tag: &str,
+    remote: bool,
+    retries: Option<u32>,
+) {
+    let repos = select_repositories(config, selection);
+    if repos.is_empty() {
+        println!("No repositories to delete the tag from.");
+        return;
+    }
+
+    println!("{} {}", "Deleting tag:".green().bold(), tag.yellow());
+    let summary = for_each_repository(&repos, |repo, path| {
+        let local = git.tags(path).iter().any(|t| t == tag);
+        if !local && !remote {
+            return (Outcome::Skipped, format!("no tag '{}'", tag));
+        }
+
+        let mut done = Vec::new();
+        if local {
+            let output = git.delete_tag(path, tag);
+            if !output.success {
+                return failure(&output);
+            }
+            done.push("deleted locally".to_string());
+        }
+
+        if remote {
+            let (remote, _) = remote_and_branch(repo);
+            let options = run_options(config, repo, retries);
+            let output = with_retries(&options, || git.push_tag(path, remote, tag, true, &options));
+            if !output.success {
+                let (outcome, error) = failure(&output);
+                done.push(format!("deleting from {} failed: {}", remote, error));
+                return (outcome, done.join(", "));
+            }
+            done.push(format!("deleted from {}", remote));
+        }
+        (Outcome::Ok, done.join(", "))
+    });
+    summary.print("Tag deletion complete!");
+}
+
+/// Prints a matrix of tags (rows) against repositories (columns).
+pub fn list_tags(config: &Config, selection: &Selection, git: &dyn GitBackend) {
+    let mut columns = Vec::new();
+    for repo in select_repositories(config, selection) {
+        let path = shellexpand::tilde(&repo.path);
+        let path = Path::new(path.as_ref());
+        if !path.exists() {
+            eprintln!(
+                "{} {}: Repository path does not exist",
+                "WARNING:".yellow().bold(),
+                repo.name
+            );
+            continue;
+        }
+        columns.push((repo.name.as_str(), git.tags(path)));
+    }
+
+    let tags: BTreeSet<&str> = columns
+        .iter()
+        .flat_map(|(_, tags)| tags)
+        .map(String::as_str)
+        .collect();
+    if tags.is_empty() {
+        println!("No tags.");
+        return;
+    }
+
+    let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    print_matrix(
+        &names,
+        tags.into_iter().map(|tag| {
+            let cells = columns
+                .iter()
+                .map(|(_, tags)| {
+                    if tags.iter().any(|t| t == tag) {
+                        "✓".green()
+                    } else {
+                        "·".dimmed()
+                    }
+                })
+                .collect();
+            (tag, cells)
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::Commit;
+    use crate::git::fake::FakeRepo;
+    use crate::testing::Fixture;
+
+    /// Two repositories with a commit each; `setup` adjusts the second one.
+    fn repositories(test: &str, setup: impl FnOnce(FakeRepo) -> FakeRepo) -> Fixture {
+        let repo = FakeRepo {
+            commits: vec![Commit {
+                sha: "4".repeat(40),
+                summary: "Release".to_string(),
+            }],
+            ..FakeRepo::new("main")
+        };
+        Fixture::new(test, [("api", repo.clone()), ("web", setup(repo))])
+    }
+
+    #[test]
+    fn tags_and_pushes_every_repository() {
+        let fixture = repositories("tag", |repo| repo);
+        let (config, git, paths) = (&fixture.config, &fixture.git, &fixture.paths);
+        let settings = TagSettings {
+            push: true,
+            ..TagSettings::default()
+        };
+
+        create_tag(config, &Selection::default(), git, "v1.0.0", &settings);
+
+        for path in paths {
+            assert_eq!(git.repo(path).unwrap().tags, ["v1.0.0"]);
+            assert!(
+                git.calls()
+                    .contains(&format!("push-tag {} origin v1.0.0 push", path.display()))
+            );
+        }
+    }
+
+    #[test]
+    fn tags_nothing_unless_every_repository_can_be_tagged() {
+        let fixture = repositories("tag-existing", |mut repo| {
+            repo.tags.push("v1.0.0".to_string());
+            repo
+        });
+        let (config, git, paths) = (&fixture.config, &fixture.git, &fixture.paths);
+
+        create_tag(
+            config,
+            &Selection::default(),
+            git,
+            "v1.0.0",
+            &TagSettings::default(),
+        );
+        assert!(git.repo(&paths[0]).unwrap().tags.is_empty());
+
+        let settings = TagSettings {
+            target: Some("no-such-branch".to_string()),
+            ..TagSettings::default()
+        };
+        create_tag(config, &Selection::default(), git, "v1.0.1", &settings);
+        assert!(git.repo(&paths[0]).unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn deletes_local_tags_and_remote_ones_on_request() {
+        let fixture = repositories("tag-delete", |mut repo| {
+            repo.tags.push("v1.0.0".to_string());
+            repo
+        });
+        let (config, git, paths) = (&fixture.config, &fixture.git, &fixture.paths);
+
+        delete_tag(config, &Selection::default(), git, "v1.0.0", true, None);
+
+        assert!(git.repo(&paths[1]).unwrap().tags.is_empty());
+        let calls = git.calls();
+        assert!(
+            !calls
+                .iter()
+                .any(|call| call.starts_with(&format!("tag-delete {}", paths[0].display())))
+        );
+        for path in paths {
+            assert!(calls.contains(&format!("push-tag {} origin v1.0.0 delete", path.display())));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `20240501-093000`, in UTC.
+pub(super) fn format_id(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc(time);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// `2024-05-01T09:30:00Z`
+pub(super) fn format_timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Splits a time into UTC calendar fields (Howard Hinnant's civil-from-days).
+fn utc(time: SystemTime) -> (i64, u32, u32, u32, u32, u32) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (days, rest) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month,
+        day,
+        (rest / 3600) as u32,
+        (rest % 3600 / 60) as u32,
+        (rest % 60) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn splits_epoch() {
+        assert_eq!(utc(UNIX_EPOCH), (1970, 1, 1, 0, 0, 0));
+    }
+
+    #[test]
+    fn handles_leap_days_and_century_rules() {
+        // 2000 is a leap year, 2100 is not
+        assert_eq!(utc(at(951_782_400)), (2000, 2, 29, 0, 0, 0));
+        assert_eq!(utc(at(4_107_542_400)), (2100, 3, 1, 0, 0, 0));
+        assert_eq!(utc(at(1_709_251_199)), (2024, 2, 29, 23, 59, 59));
+    }
+
+    #[test]
+    fn times_before_the_epoch_count_as_the_epoch() {
+        assert_eq!(
+            utc(UNIX_EPOCH - Duration::from_secs(60)),
+            (1970, 1, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn formats_ids_and_timestamps() {
+        let time = at(1_714_555_800);
+        assert_eq!(format_id(time), "20240501-093000");
+        assert_eq!(format_timestamp(time), "2024-05-01T09:30:00Z");
+    }
+}
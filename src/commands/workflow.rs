@@ -0,0 +1,466 @@
+use super::hooks::run_hooks;
+use super::output::{OutputMode, RepoOutput};
+use super::parallel;
+use super::run::{Invocation, RunSettings, run_command, run_in_repository};
+use super::summary::{Outcome, Summary};
+use super::sync::{remote_and_branch, run_options};
+use crate::config::{
+    Config, Hook, OnFailure, Repository, Selection, StepDefinition, TagFilter, Workflow,
+    WorkflowStep, select_repositories,
+};
+use crate::git::{GitBackend, GitOutput, hints, with_retries_reporting};
+use crate::process;
+use colored::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How `gitpower do` runs a named command or workflow.
+#[derive(Debug, Clone)]
+pub struct DoSettings {
+    /// Number of repositories to work on at the same time
+    pub jobs: usize,
+    pub output: OutputMode,
+    pub retries: Option<u32>,
+    /// Only show what would run
+    pub dry_run: bool,
+    /// Where named command runs are recorded for `gitpower runs`
+    pub runs_dir: PathBuf,
+}
+
+/// A workflow step, checked and resolved against the config.
+enum Step {
+    Fetch,
+    Pull,
+    EnsureClean,
+    Checkout(String),
+    Run(Invocation),
+}
+
+impl Step {
+    fn describe(&self) -> String {
+        match self {
+            Step::Fetch => "fetch".to_string(),
+            Step::Pull => "pull".to_string(),
+            Step::EnsureClean => "ensure clean".to_string(),
+            Step::Checkout(branch) => format!("checkout {}", branch),
+            Step::Run(Invocation::Shell { command, .. }) => format!("run: {}", command),
+            Step::Run(Invocation::Argv(argv)) => format!("run: {}", argv.join(" ")),
+        }
+    }
+}
+
+/// Runs the command or workflow called `name`. Repositories and tags given on
+/// the command line replace the ones configured for it.
+pub fn run_named(
+    config: &Config,
+    git: &dyn GitBackend,
+    name: &str,
+    selection: &Selection,
+    settings: &DoSettings,
+) {
+    match (config.command(name), config.workflow(name)) {
+        (Some(_), Some(_)) => eprintln!(
+            "{} '{}' is defined both as a command and as a workflow; rename one of them",
+            "ERROR:".red().bold(),
+            name
+        ),
+        (Some(command), None) => {
+            let selection = with_defaults(selection, &command.repos, &command.tags);
+            let invocation = Invocation::Shell {
+                shell: command.shell.clone().unwrap_or_else(|| "sh".to_string()),
+                command: command.run.clone(),
+            };
+            let run_settings = RunSettings {
+                jobs: settings.jobs,
+                output: settings.output,
+                dry_run: settings.dry_run,
+                only_if: None,
+                fail_fast: false,
+                max_failures: None,
+                output_dir: None,
+                runs_dir: settings.runs_dir.clone(),
+            };
+            run_command(config, git, &invocation, &selection, &run_settings);
+        }
+        (None, Some(workflow)) => {
+            let selection = with_defaults(selection, &workflow.repos, &workflow.tags);
+            run_workflow(config, git, workflow, &selection, settings);
+        }
+        (None, None) => eprintln!(
+            "{} No command or workflow named '{}'; run 'gitpower do' to list them",
+            "ERROR:".red().bold(),
+            name
+        ),
+    }
+}
+
+/// Lists the configured commands and workflows.
+pub fn list_named(config: &Config) {
+    let commands = config.commands.as_deref().unwrap_or_default();
+    let workflows = config.workflows.as_deref().unwrap_or_default();
+
+    if commands.is_empty() && workflows.is_empty() {
+        println!("No commands or workflows configured.");
+        return;
+    }
+
+    if !commands.is_empty() {
+        println!("{}", "Commands:".green().bold());
+        for command in commands {
+            println!("  {} - {}", command.name.yellow(), command.run);
+            print_details(&command.description, &command.repos, &command.tags);
+        }
+    }
+
+    if !workflows.is_empty() {
+        if !commands.is_empty() {
+            println!();
+        }
+        println!("{}", "Workflows:".green().bold());
+        for workflow in workflows {
+            let steps = workflow.steps.len();
+            println!(
+                "  {} - {} step{}",
+                workflow.name.yellow(),
+                steps,
+                if steps == 1 { "" } else { "s" }
+            );
+            print_details(&workflow.description, &workflow.repos, &workflow.tags);
+        }
+    }
+}
+
+fn print_details(
+    description: &Option<String>,
+    repos: &Option<Vec<String>>,
+    tags: &Option<Vec<String>>,
+) {
+    if let Some(description) = description {
+        println!("    {}", description);
+    }
+    if let Some(repos) = repos {
+        println!("    Repos: {}", repos.join(", "));
+    }
+    if let Some(tags) = tags {
+        println!("    Tags: {}", tags.join(", ").cyan());
+    }
+}
+
+/// `selection`, with names and tags the user did not give taken from the configured defaults.
+fn with_defaults<'a>(
+    selection: &Selection<'a>,
+    repos: &'a Option<Vec<String>>,
+    tags: &'a Option<Vec<String>>,
+) -> Selection<'a> {
+    Selection {
+        names: if selection.names.is_empty() {
+            repos.iter().flatten().map(String::as_str).collect()
+        } else {
+            selection.names.clone()
+        },
+        tags: if selection.tags.is_empty() {
+            TagFilter::new(tags.iter().flatten().map(String::as_str), [])
+        } else {
+            selection.tags.clone()
+        },
+    }
+}
+
+fn run_workflow(
+    config: &Config,
+    git: &dyn GitBackend,
+    workflow: &Workflow,
+    selection: &Selection,
+    settings: &DoSettings,
+) {
+    let steps = match resolve(config, workflow) {
+        Ok(steps) => steps,
+        Err(e) => {
+            eprintln!(
+                "{} Invalid workflow '{}': {}",
+                "ERROR:".red().bold(),
+                workflow.name,
+                e
+            );
+            return;
+        }
+    };
+
+    let repos = select_repositories(config, selection);
+    if repos.is_empty() {
+        println!("No repositories to run workflow in.");
+        return;
+    }
+
+    if settings.dry_run {
+        preview(workflow, &steps, &repos);
+        return;
+    }
+
+    println!(
+        "{} {}",
+        "Running workflow:".green().bold(),
+        workflow.name.yellow()
+    );
+
+    let width = repos
+        .iter()
+        .map(|repo| repo.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let indexed: Vec<(usize, &Repository)> = repos.iter().copied().enumerate().collect();
+    let aborted = AtomicBool::new(false);
+
+    let outcomes = parallel::map(&indexed, settings.jobs, |(index, repo)| {
+        if process::is_cancelled() || aborted.load(Ordering::SeqCst) {
+            return Outcome::NotRun;
+        }
+
+        let output = RepoOutput::new(settings.output, repo, *index, width);
+        let outcome = run_steps(config, git, &steps, repo, &output, settings, &aborted);
+        output.finish();
+        outcome
+    });
+
+    let mut summary = Summary::new();
+    for (repo, outcome) in repos.iter().zip(&outcomes) {
+        summary.record(&repo.name, *outcome);
+    }
+    if aborted.load(Ordering::SeqCst) && outcomes.contains(&Outcome::NotRun) {
+        summary.note("A step with on_failure: abort failed, remaining repositories were not run");
+    }
+    summary.print("Workflow complete!");
+}
+
+/// Checks every step and looks up the commands they refer to.
+fn resolve(config: &Config, workflow: &Workflow) -> Result<Vec<(Step, OnFailure)>, String> {
+    if workflow.steps.is_empty() {
+        return Err("it has no steps".to_string());
+    }
+
+    workflow
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| resolve_step(config, step).map_err(|e| format!("step {}: {}", i + 1, e)))
+        .collect()
+}
+
+fn resolve_step(config: &Config, step: &WorkflowStep) -> Result<(Step, OnFailure), String> {
+    let definition = match step {
+        WorkflowStep::Action(action) => &StepDefinition {
+            action: Some(action.clone()),
+            ..StepDefinition::default()
+        },
+        WorkflowStep::Detailed(definition) => definition,
+    };
+
+    let kinds = [
+        definition.action.is_some(),
+        definition.checkout.is_some(),
+        definition.run.is_some(),
+        definition.command.is_some(),
+    ];
+    if kinds.iter().filter(|set| **set).count() != 1 {
+        return Err("expected exactly one of action, checkout, run or command".to_string());
+    }
+
+    let step = if let Some(action) = &definition.action {
+        match action.as_str() {
+            "fetch" => Step::Fetch,
+            "pull" => Step::Pull,
+            "ensure-clean" => Step::EnsureClean,
+            other => {
+                return Err(format!(
+                    "unknown action '{}'; expected fetch, pull or ensure-clean",
+                    other
+                ));
+            }
+        }
+    } else if let Some(branch) = &definition.checkout {
+        Step::Checkout(branch.clone())
+    } else if let Some(command) = &definition.run {
+        Step::Run(Invocation::Shell {
+            shell: "sh".to_string(),
+            command: command.clone(),
+        })
+    } else {
+        let name = definition.command.as_deref().unwrap_or_default();
+        let command = config
+            .command(name)
+            .ok_or_else(|| format!("no command named '{}'", name))?;
+        Step::Run(Invocation::Shell {
+            shell: command.shell.clone().unwrap_or_else(|| "sh".to_string()),
+            command: command.run.clone(),
+        })
+    };
+
+    Ok((step, definition.on_failure.unwrap_or_default()))
+}
+
+fn preview(workflow: &Workflow, steps: &[(Step, OnFailure)], repos: &[&Repository]) {
+    println!(
+        "{} {}",
+        "Dry run, workflow is not executed:".yellow().bold(),
+        workflow.name
+    );
+    for (i, (step, on_failure)) in steps.iter().enumerate() {
+        let note = match on_failure {
+            OnFailure::Stop => "",
+            OnFailure::Continue => " (continue on failure)",
+            OnFailure::Abort => " (abort on failure)",
+        };
+        println!("  {}. {}{}", i + 1, step.describe(), note.dimmed());
+    }
+    println!(
+        "\nRepositories: {}",
+        repos
+            .iter()
+            .map(|repo| repo.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
+
+/// Runs the steps in one repository. The first failure decides the outcome.
+fn run_steps(
+    config: &Config,
+    git: &dyn GitBackend,
+    steps: &[(Step, OnFailure)],
+    repo: &Repository,
+    output: &RepoOutput,
+    settings: &DoSettings,
+    aborted: &AtomicBool,
+) -> Outcome {
+    let path = shellexpand::tilde(&repo.path);
+    let repo_path = Path::new(path.as_ref());
+    if !repo_path.exists() {
+        output.error_line(&format!(
+            "{} Repository path does not exist",
+            "ERROR:".red().bold()
+        ));
+        return Outcome::Failed;
+    }
+
+    let mut result = Outcome::Ok;
+    for (i, (step, on_failure)) in steps.iter().enumerate() {
+        if process::is_cancelled() {
+            return Outcome::Cancelled;
+        }
+
+        output.line(&format!(
+            "{} {}",
+            format!("[{}/{}]", i + 1, steps.len()).cyan(),
+            step.describe()
+        ));
+        let outcome = run_step(config, git, step, repo, repo_path, output, settings);
+        if outcome.is_ok() {
+            continue;
+        }
+        if outcome == Outcome::Cancelled {
+            return outcome;
+        }
+
+        match on_failure {
+            OnFailure::Continue => {
+                if result.is_ok() {
+                    result = outcome;
+                }
+            }
+            OnFailure::Stop | OnFailure::Abort => {
+                if *on_failure == OnFailure::Abort {
+                    aborted.store(true, Ordering::SeqCst);
+                }
+                if i + 1 < steps.len() {
+                    output.line(&format!(
+                        "{} Skipping the remaining steps",
+                        "WARNING:".yellow().bold()
+                    ));
+                }
+                return outcome;
+            }
+        }
+    }
+    result
+}
+
+fn run_step(
+    config: &Config,
+    git: &dyn GitBackend,
+    step: &Step,
+    repo: &Repository,
+    repo_path: &Path,
+    output: &RepoOutput,
+    settings: &DoSettings,
+) -> Outcome {
+    let (remote, configured_branch) = remote_and_branch(repo);
+    let options = run_options(config, repo, settings.retries);
+    let on_retry = |line: &str| output.line(line);
+
+    match step {
+        Step::Fetch => report_git(
+            output,
+            &with_retries_reporting(&options, &on_retry, || {
+                git.fetch(repo_path, remote, &options)
+            }),
+        ),
+        Step::Pull => {
+            let on_line = |line: &str| output.line(line);
+            let outcome = run_hooks(config, repo, repo_path, Hook::PrePull, &on_line);
+            if !outcome.is_ok() {
+                return outcome;
+            }
+
+            // Pull whatever an earlier step checked out
+            let branch = git
+                .current_branch(repo_path)
+                .unwrap_or_else(|| configured_branch.to_string());
+            let outcome = report_git(
+                output,
+                &with_retries_reporting(&options, &on_retry, || {
+                    git.pull(repo_path, remote, &branch, &options)
+                }),
+            );
+            if !outcome.is_ok() {
+                return outcome;
+            }
+            run_hooks(config, repo, repo_path, Hook::PostPull, &on_line)
+        }
+        Step::EnsureClean => match git.status(repo_path) {
+            Ok(changes) if changes.is_empty() => Outcome::Ok,
+            Ok(changes) => {
+                output.error_line(&format!(
+                    "{} {} uncommitted change{}",
+                    "ERROR:".red().bold(),
+                    changes.len(),
+                    if changes.len() == 1 { "" } else { "s" }
+                ));
+                Outcome::Failed
+            }
+            Err(e) => {
+                output.error_line(&format!("{} {}", "ERROR:".red().bold(), e));
+                Outcome::Failed
+            }
+        },
+        Step::Checkout(branch) => report_git(output, &git.checkout(repo_path, branch)),
+        Step::Run(invocation) => {
+            run_in_repository(config, git, invocation, None, repo, output, None)
+        }
+    }
+}
+
+/// Prints a git operation's output to `output` and classifies it.
+fn report_git(output: &RepoOutput, result: &GitOutput) -> Outcome {
+    for line in result.stdout.lines().filter(|line| !line.trim().is_empty()) {
+        output.line(line);
+    }
+    for line in result.stderr.lines().filter(|line| !line.trim().is_empty()) {
+        output.error_line(line);
+    }
+
+    for hint in hints(result) {
+        output.line(&hint);
+    }
+
+    Outcome::of(result)
+}
@@ -0,0 +1,51 @@
+//! In-place editing of JSON documents, keeping the original key order.
+
+use serde_json::Value;
+
+pub fn apply(content: &str, target: &serde_yaml::Value) -> Result<String, String> {
+    let mut doc: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let target = serde_json::to_value(target).map_err(|e| e.to_string())?;
+
+    patch(&mut doc, &target);
+
+    serde_json::to_string_pretty(&doc)
+        .map(|json| json + "\n")
+        .map_err(|e| e.to_string())
+}
+
+fn patch(node: &mut Value, target: &Value) {
+    match (node, target) {
+        (Value::Object(node), Value::Object(target)) => {
+            node.retain(|key, _| target.contains_key(key));
+            for (key, value) in target {
+                match node.get_mut(key) {
+                    Some(existing) => patch(existing, value),
+                    None => {
+                        node.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(node), Value::Array(target)) if is_named(node) && is_named(target) => {
+            node.retain(|item| target.iter().any(|t| t["name"] == item["name"]));
+            for value in target {
+                match node.iter_mut().find(|item| item["name"] == value["name"]) {
+                    Some(existing) => patch(existing, value),
+                    None => node.push(value.clone()),
+                }
+            }
+        }
+        (node, target) => {
+            if node != target {
+                *node = target.clone();
+            }
+        }
+    }
+}
+
+/// Lists of objects keyed by `name` are matched item by item instead of being replaced.
+fn is_named(items: &[Value]) -> bool {
+    items
+        .iter()
+        .all(|item| item.get("name").is_some_and(Value::is_string))
+}
@@ -0,0 +1,58 @@
+//! Format-aware, in-place edits of config files.
+//!
+//! Instead of re-serializing the whole `Config`, writers describe the desired
+//! document as a value and each format module patches only what changed, so
+//! comments, key order and formatting of the user's file are preserved.
+
+mod json;
+mod toml;
+mod yaml;
+
+use super::ConfigFormat;
+
+/// Rewrites `content` so it describes `target`, leaving unchanged parts of the document untouched.
+pub fn apply(
+    format: ConfigFormat,
+    content: &str,
+    target: &serde_yaml::Value,
+) -> Result<String, String> {
+    let updated = match format {
+        ConfigFormat::Yaml => yaml::apply(content, target)?,
+        ConfigFormat::Toml => toml::apply(content, target)?,
+        ConfigFormat::Json => json::apply(content, target)?,
+    };
+
+    // Never hand back a document that doesn't read back as the requested value
+    let parsed: serde_yaml::Value = format.parse(&updated).map_err(|e| e.to_string())?;
+    if !same_value(&parsed, target) {
+        return Err("edited document does not match the updated config".to_string());
+    }
+
+    Ok(updated)
+}
+
+/// Compares two documents, treating absent and `null` mapping entries as equal
+/// since TOML has no null and serializers skip empty optional fields.
+fn same_value(a: &serde_yaml::Value, b: &serde_yaml::Value) -> bool {
+    use serde_yaml::Value;
+
+    match (a, b) {
+        (Value::Mapping(a), Value::Mapping(b)) => {
+            let present = |map: &serde_yaml::Mapping| {
+                map.iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .map(|(k, _)| k.clone())
+                    .collect::<Vec<_>>()
+            };
+            let keys = present(a);
+            keys.len() == present(b).len()
+                && keys
+                    .iter()
+                    .all(|k| b.get(k).is_some_and(|v| same_value(&a[k], v)))
+        }
+        (Value::Sequence(a), Value::Sequence(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        _ => a == b,
+    }
+}
@@ -0,0 +1,222 @@
+//! In-place editing of TOML documents through `toml_edit`, which keeps comments and layout.
+
+use serde_yaml::{Mapping, Value};
+use toml_edit::{Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, TableLike};
+
+pub fn apply(content: &str, target: &Value) -> Result<String, String> {
+    let mut doc: DocumentMut = content
+        .parse()
+        .map_err(|e: toml_edit::TomlError| e.to_string())?;
+    let current: Mapping = toml::from_str(content).map_err(|e| e.to_string())?;
+    let target = target
+        .as_mapping()
+        .ok_or("top level of the config is not a table")?;
+
+    patch_table(doc.as_table_mut(), &current, target)?;
+
+    Ok(doc.to_string())
+}
+
+fn patch_table(
+    table: &mut dyn TableLike,
+    current: &Mapping,
+    target: &Mapping,
+) -> Result<(), String> {
+    for key in current.keys() {
+        if !target.contains_key(key)
+            && let Some(key) = key.as_str()
+        {
+            table.remove(key);
+        }
+    }
+
+    for (key, value) in target {
+        let key = key.as_str().ok_or("TOML keys must be strings")?;
+        match (table.get_mut(key), current.get(key)) {
+            (Some(_), _) if value.is_null() => {
+                table.remove(key);
+            }
+            (Some(item), Some(existing)) => patch_item(item, existing, value)?,
+            _ if value.is_null() => {}
+            _ => {
+                table.insert(key, to_item(value)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn patch_item(item: &mut Item, current: &Value, target: &Value) -> Result<(), String> {
+    if current == target {
+        return Ok(());
+    }
+
+    match (item, current, target) {
+        (Item::Table(table), Value::Mapping(current), Value::Mapping(target)) => {
+            patch_table(table, current, target)
+        }
+        (
+            Item::Value(toml_edit::Value::InlineTable(table)),
+            Value::Mapping(current),
+            Value::Mapping(target),
+        ) => patch_table(table, current, target),
+        (Item::ArrayOfTables(tables), Value::Sequence(current), Value::Sequence(target))
+            if is_named(current) && is_named(target) =>
+        {
+            patch_tables(tables, current, target)
+        }
+        (
+            Item::Value(toml_edit::Value::Array(array)),
+            Value::Sequence(current),
+            Value::Sequence(target),
+        ) => patch_array(array, current, target),
+        (Item::Value(value), _, target) => {
+            let decor = value.decor().clone();
+            *value = to_value(target)?;
+            *value.decor_mut() = decor;
+            Ok(())
+        }
+        (item, _, target) => {
+            *item = to_item(target)?;
+            Ok(())
+        }
+    }
+}
+
+/// Arrays of tables keyed by `name` (repositories, groups) are edited table by table.
+fn patch_tables(
+    tables: &mut ArrayOfTables,
+    current: &[Value],
+    target: &[Value],
+) -> Result<(), String> {
+    if tables.len() != current.len() {
+        return Err("array of tables is out of sync with the document".to_string());
+    }
+
+    let mut kept = Vec::new();
+    for (i, existing) in current.iter().enumerate().rev() {
+        match target
+            .iter()
+            .find(|value| value["name"] == existing["name"])
+        {
+            Some(value) => kept.push((existing, value)),
+            None => tables.remove(i),
+        }
+    }
+
+    // `kept` was collected back to front, so indices shift down as removals happened above them
+    for (position, (existing, value)) in kept.iter().rev().enumerate() {
+        let table = tables
+            .get_mut(position)
+            .ok_or("array of tables is out of sync")?;
+        match (existing, value) {
+            (Value::Mapping(existing), Value::Mapping(value)) => {
+                patch_table(table, existing, value)?
+            }
+            _ => return Err("array of tables contains a non-table item".to_string()),
+        }
+    }
+
+    for value in target {
+        if !current
+            .iter()
+            .any(|existing| existing["name"] == value["name"])
+        {
+            let map = value
+                .as_mapping()
+                .ok_or("array of tables contains a non-table item")?;
+            tables.push(to_table(map)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn patch_array(array: &mut Array, current: &[Value], target: &[Value]) -> Result<(), String> {
+    if array.len() != current.len() {
+        return Err("array is out of sync with the document".to_string());
+    }
+
+    let mut matched = vec![false; target.len()];
+    for (i, existing) in current.iter().enumerate().rev() {
+        match target
+            .iter()
+            .enumerate()
+            .position(|(j, value)| !matched[j] && value == existing)
+        {
+            Some(j) => matched[j] = true,
+            None => {
+                array.remove(i);
+            }
+        }
+    }
+
+    for (value, _) in target.iter().zip(matched).filter(|(_, matched)| !matched) {
+        array.push(to_value(value)?);
+    }
+
+    Ok(())
+}
+
+fn is_named(items: &[Value]) -> bool {
+    items.iter().all(|item| item["name"].is_string())
+}
+
+fn to_item(value: &Value) -> Result<Item, String> {
+    match value {
+        Value::Mapping(map) => Ok(Item::Table(to_table(map)?)),
+        Value::Sequence(seq) if !seq.is_empty() && seq.iter().all(Value::is_mapping) => {
+            let mut tables = ArrayOfTables::new();
+            for value in seq {
+                tables.push(to_table(value.as_mapping().expect("checked above"))?);
+            }
+            Ok(Item::ArrayOfTables(tables))
+        }
+        value => Ok(Item::Value(to_value(value)?)),
+    }
+}
+
+fn to_table(map: &Mapping) -> Result<Table, String> {
+    let mut table = Table::new();
+    for (key, value) in map {
+        if value.is_null() {
+            continue;
+        }
+        let key = key.as_str().ok_or("TOML keys must be strings")?;
+        table.insert(key, to_item(value)?);
+    }
+    Ok(table)
+}
+
+fn to_value(value: &Value) -> Result<toml_edit::Value, String> {
+    Ok(match value {
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => i.into(),
+            (None, Some(f)) => f.into(),
+            _ => return Err(format!("number {} does not fit in TOML", n)),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Sequence(seq) => {
+            let mut array = Array::new();
+            for value in seq {
+                array.push(to_value(value)?);
+            }
+            toml_edit::Value::Array(array)
+        }
+        Value::Mapping(map) => {
+            let mut table = InlineTable::new();
+            for (key, value) in map {
+                if value.is_null() {
+                    continue;
+                }
+                let key = key.as_str().ok_or("TOML keys must be strings")?;
+                table.insert(key, to_value(value)?);
+            }
+            toml_edit::Value::InlineTable(table)
+        }
+        Value::Null => return Err("TOML cannot represent null values".to_string()),
+        Value::Tagged(tagged) => to_value(&tagged.value)?,
+    })
+}
@@ -0,0 +1,653 @@
+//! In-place editing of block-style YAML documents.
+//!
+//! The document is scanned into a light tree of mappings and sequences that
+//! records which lines every key and item occupies. Patching walks that tree
+//! alongside the parsed values and only rewrites the lines of nodes whose value
+//! actually changed, so comments, key order and blank lines elsewhere survive.
+
+use serde_yaml::{Mapping, Value};
+
+pub fn apply(content: &str, target: &Value) -> Result<String, String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let current: Value = serde_yaml::from_str(content).map_err(|e| e.to_string())?;
+
+    let root = match first_content_line(&lines, 0, lines.len()) {
+        Some(start) => Scanner { lines: &lines }.node(start, 0, lines.len()),
+        None => Node::Empty,
+    };
+
+    let mut patcher = Patcher {
+        lines: &lines,
+        edits: Vec::new(),
+        seq_offset: root.seq_offset().unwrap_or(2),
+    };
+
+    match (&root, &current, target) {
+        (Node::Mapping(node), Value::Mapping(current), Value::Mapping(target)) => {
+            patcher.mapping(node, current, target)?
+        }
+        _ => return Err("top level of the document is not a block mapping".to_string()),
+    }
+
+    Ok(patcher.finish(content.ends_with('\n')))
+}
+
+struct Slot {
+    /// First line of the comments directly attached above the slot
+    lead: usize,
+    /// Line holding the key or the dash
+    start: usize,
+    /// One past the last content line belonging to the slot
+    end: usize,
+    /// Column of the key or the dash
+    col: usize,
+    key: Option<Value>,
+    /// Comment trailing an inline value on the slot's first line
+    comment: Option<String>,
+    value: Node,
+}
+
+struct Block {
+    col: usize,
+    slots: Vec<Slot>,
+    /// One past the last content line of the block, where new slots are appended
+    end: usize,
+}
+
+enum Node {
+    Mapping(Block),
+    Sequence(Block),
+    Scalar,
+    Empty,
+}
+
+impl Node {
+    /// Finds how far block sequences are indented relative to their parent key.
+    fn seq_offset(&self) -> Option<usize> {
+        let block = match self {
+            Node::Mapping(block) | Node::Sequence(block) => block,
+            _ => return None,
+        };
+        block
+            .slots
+            .iter()
+            .find_map(|slot| match (&slot.key, &slot.value) {
+                (Some(_), Node::Sequence(seq)) => Some(seq.col - slot.col),
+                _ => slot.value.seq_offset(),
+            })
+    }
+}
+
+struct Scanner<'a> {
+    lines: &'a [&'a str],
+}
+
+impl Scanner<'_> {
+    /// Scans the node whose first token sits at `col` on line `start`, bounded by `end`.
+    fn node(&self, start: usize, col: usize, end: usize) -> Node {
+        let text = &self.lines[start][col..];
+        if is_dash(text) {
+            Node::Sequence(self.block(start, col, end, true))
+        } else if parse_key(text).is_some() {
+            Node::Mapping(self.block(start, col, end, false))
+        } else {
+            Node::Scalar
+        }
+    }
+
+    fn block(&self, start: usize, col: usize, end: usize, sequence: bool) -> Block {
+        let is_slot_start = |line: &str| {
+            is_content(line)
+                && indent(line) == col
+                && if sequence {
+                    is_dash(&line[col..])
+                } else {
+                    parse_key(&line[col..]).is_some()
+                }
+        };
+
+        let starts: Vec<usize> = std::iter::once(start)
+            .chain((start + 1..end).filter(|&i| is_slot_start(self.lines[i])))
+            .collect();
+
+        let mut slots = Vec::with_capacity(starts.len());
+        for (i, &slot_start) in starts.iter().enumerate() {
+            let next = starts.get(i + 1).copied().unwrap_or(end);
+            let slot_end = last_content_line(self.lines, slot_start, next) + 1;
+
+            let mut lead = slot_start;
+            let floor = slots.last().map_or(start, |prev: &Slot| prev.end);
+            while lead > floor && is_comment(self.lines[lead - 1]) {
+                lead -= 1;
+            }
+
+            slots.push(if sequence {
+                self.item(slot_start, col, slot_end, lead)
+            } else {
+                self.entry(slot_start, col, slot_end, lead)
+            });
+        }
+
+        let end = slots.last().map_or(start + 1, |slot| slot.end);
+        Block { col, slots, end }
+    }
+
+    fn entry(&self, start: usize, col: usize, end: usize, lead: usize) -> Slot {
+        let line = self.lines[start];
+        let (key, rest) = parse_key(&line[col..]).expect("slot starts with a key");
+        let (inline, comment) = split_comment(rest);
+
+        let value = if !inline.trim().is_empty() {
+            Node::Scalar
+        } else {
+            match first_content_line(self.lines, start + 1, end) {
+                Some(child)
+                    if indent(self.lines[child]) > col
+                        || (indent(self.lines[child]) == col
+                            && is_dash(&self.lines[child][col..])) =>
+                {
+                    self.node(child, indent(self.lines[child]), end)
+                }
+                _ => Node::Empty,
+            }
+        };
+
+        Slot {
+            lead,
+            start,
+            end,
+            col,
+            key: Some(key),
+            comment,
+            value,
+        }
+    }
+
+    fn item(&self, start: usize, col: usize, end: usize, lead: usize) -> Slot {
+        let line = self.lines[start];
+        let after_dash = &line[col + 1..];
+        let (inline, comment) = split_comment(after_dash);
+
+        let value = if inline.trim().is_empty() {
+            match first_content_line(self.lines, start + 1, end) {
+                Some(child) if indent(self.lines[child]) > col => {
+                    self.node(child, indent(self.lines[child]), end)
+                }
+                _ => Node::Empty,
+            }
+        } else {
+            let value_col = col + 1 + indent(after_dash);
+            self.node(start, value_col, end)
+        };
+
+        Slot {
+            lead,
+            start,
+            end,
+            col,
+            key: None,
+            comment,
+            value,
+        }
+    }
+}
+
+struct Edit {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+struct Patcher<'a> {
+    lines: &'a [&'a str],
+    edits: Vec<Edit>,
+    seq_offset: usize,
+}
+
+impl Patcher<'_> {
+    fn mapping(&mut self, node: &Block, current: &Mapping, target: &Mapping) -> Result<(), String> {
+        let find = |key: &Value| {
+            node.slots
+                .iter()
+                .find(|slot| slot.key.as_ref() == Some(key))
+        };
+
+        if node.slots.len() != current.len() {
+            return Err("mapping keys could not be matched to the document".to_string());
+        }
+
+        let target_keys: Vec<&Value> = target.keys().collect();
+        for (i, (key, value)) in target.iter().enumerate() {
+            match find(key) {
+                Some(slot) => {
+                    let existing = current.get(key).ok_or("mapping keys are out of sync")?;
+                    if existing != value {
+                        self.slot(slot, existing, value)?;
+                    }
+                }
+                None => {
+                    // Keep the target's key order by inserting before the next key that already exists
+                    let at = target_keys[i + 1..]
+                        .iter()
+                        .find_map(|next| find(next))
+                        .map_or(node.end, |slot| slot.lead);
+                    let lines = self.emit_entry(key, value, node.col);
+                    self.insert(at, lines);
+                }
+            }
+        }
+
+        for key in current.keys() {
+            if !target.contains_key(key)
+                && let Some(slot) = find(key)
+            {
+                self.remove(slot);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sequence(
+        &mut self,
+        node: &Block,
+        current: &[Value],
+        target: &[Value],
+    ) -> Result<(), String> {
+        if node.slots.len() != current.len() {
+            return Err("sequence items could not be matched to the document".to_string());
+        }
+
+        let mut matched = vec![false; target.len()];
+
+        for (slot, existing) in node.slots.iter().zip(current) {
+            let found = match item_name(existing) {
+                Some(name) => target
+                    .iter()
+                    .enumerate()
+                    .position(|(j, value)| !matched[j] && item_name(value) == Some(name)),
+                None => target
+                    .iter()
+                    .enumerate()
+                    .position(|(j, value)| !matched[j] && value == existing),
+            };
+
+            match found {
+                Some(j) => {
+                    matched[j] = true;
+                    if &target[j] != existing {
+                        self.slot(slot, existing, &target[j])?;
+                    }
+                }
+                None => self.remove(slot),
+            }
+        }
+
+        for (value, _) in target
+            .iter()
+            .zip(&matched)
+            .filter(|(_, matched)| !**matched)
+        {
+            let lines = self.emit_item(value, node.col);
+            self.insert(node.end, lines);
+        }
+
+        Ok(())
+    }
+
+    fn slot(&mut self, slot: &Slot, current: &Value, target: &Value) -> Result<(), String> {
+        match (&slot.value, current, target) {
+            (Node::Mapping(node), Value::Mapping(current), Value::Mapping(target))
+                if !target.is_empty() =>
+            {
+                self.mapping(node, current, target)
+            }
+            (Node::Sequence(node), Value::Sequence(current), Value::Sequence(target))
+                if !target.is_empty() =>
+            {
+                self.sequence(node, current, target)
+            }
+            _ => {
+                let mut lines = match &slot.key {
+                    Some(key) => self.emit_entry(key, target, slot.col),
+                    None => self.emit_item(target, slot.col),
+                };
+                // A comment after a scalar that stays a scalar still describes it
+                if let (Node::Scalar, Some(comment), [line]) =
+                    (&slot.value, &slot.comment, lines.as_mut_slice())
+                {
+                    line.push_str(comment);
+                }
+                self.edits.push(Edit {
+                    start: slot.start,
+                    end: slot.end,
+                    lines,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn insert(&mut self, at: usize, lines: Vec<String>) {
+        self.edits.push(Edit {
+            start: at,
+            end: at,
+            lines,
+        });
+    }
+
+    fn remove(&mut self, slot: &Slot) {
+        self.edits.push(Edit {
+            start: slot.lead,
+            end: slot.end,
+            lines: Vec::new(),
+        });
+    }
+
+    fn emit_entry(&self, key: &Value, value: &Value, col: usize) -> Vec<String> {
+        let pad = " ".repeat(col);
+        let key = scalar(key);
+        match value {
+            Value::Mapping(map) if !map.is_empty() => {
+                let mut lines = vec![format!("{}{}:", pad, key)];
+                lines.extend(self.emit_mapping(map, col + 2));
+                lines
+            }
+            Value::Sequence(seq) if !seq.is_empty() => {
+                let mut lines = vec![format!("{}{}:", pad, key)];
+                lines.extend(self.emit_sequence(seq, col + self.seq_offset));
+                lines
+            }
+            _ => vec![format!("{}{}: {}", pad, key, scalar(value))],
+        }
+    }
+
+    fn emit_item(&self, value: &Value, col: usize) -> Vec<String> {
+        let mut lines = match value {
+            Value::Mapping(map) if !map.is_empty() => self.emit_mapping(map, col + 2),
+            Value::Sequence(seq) if !seq.is_empty() => self.emit_sequence(seq, col + 2),
+            _ => return vec![format!("{}- {}", " ".repeat(col), scalar(value))],
+        };
+        // The first nested line shares the dash's line
+        lines[0].replace_range(col..col + 2, "- ");
+        lines
+    }
+
+    fn emit_mapping(&self, map: &Mapping, col: usize) -> Vec<String> {
+        map.iter()
+            .flat_map(|(key, value)| self.emit_entry(key, value, col))
+            .collect()
+    }
+
+    fn emit_sequence(&self, seq: &[Value], col: usize) -> Vec<String> {
+        seq.iter()
+            .flat_map(|value| self.emit_item(value, col))
+            .collect()
+    }
+
+    fn finish(mut self, trailing_newline: bool) -> String {
+        // Insertions sort before a replacement starting on the same line
+        self.edits.sort_by_key(|edit| (edit.start, edit.end));
+
+        let mut output: Vec<String> = Vec::with_capacity(self.lines.len());
+        let mut cursor = 0;
+        for edit in self.edits {
+            if edit.start < cursor {
+                continue;
+            }
+            output.extend(self.lines[cursor..edit.start].iter().map(|l| l.to_string()));
+            output.extend(edit.lines);
+            cursor = edit.end;
+        }
+        output.extend(self.lines[cursor..].iter().map(|l| l.to_string()));
+
+        let mut text = output.join("\n");
+        if trailing_newline {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Name used to match list items across edits, e.g. repositories and groups.
+fn item_name(value: &Value) -> Option<&str> {
+    value.get("name").and_then(Value::as_str)
+}
+
+fn scalar(value: &Value) -> String {
+    match serde_yaml::to_string(value) {
+        Ok(text) if !text.trim_end().contains('\n') => text.trim_end().to_string(),
+        // Multi-line strings would need block scalars; a JSON string is valid YAML too
+        _ => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#') && trimmed != "---"
+}
+
+fn is_dash(text: &str) -> bool {
+    text == "-" || text.starts_with("- ") || text.trim_end() == "-"
+}
+
+fn first_content_line(lines: &[&str], start: usize, end: usize) -> Option<usize> {
+    (start..end).find(|&i| is_content(lines[i]))
+}
+
+fn last_content_line(lines: &[&str], start: usize, end: usize) -> usize {
+    (start..end)
+        .rev()
+        .find(|&i| is_content(lines[i]))
+        .unwrap_or(start)
+}
+
+/// Splits `key: rest` into the parsed key and everything after the colon.
+fn parse_key(text: &str) -> Option<(Value, &str)> {
+    let first = text.chars().next()?;
+    if "-[{#&*!|>?%@`".contains(first) {
+        return None;
+    }
+
+    let key_end = if first == '"' || first == '\'' {
+        text[1..].find(first)? + 2
+    } else {
+        text.char_indices()
+            .find(|&(i, c)| c == ':' && text[i + 1..].chars().next().is_none_or(|n| n == ' '))?
+            .0
+    };
+
+    let rest = text[key_end..].strip_prefix(':')?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+
+    let key = serde_yaml::from_str(text[..key_end].trim_end()).ok()?;
+    Some((key, rest))
+}
+
+/// Separates a trailing `# comment` (including its leading whitespace) from the value text.
+fn split_comment(text: &str) -> (&str, Option<String>) {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if previous == ' ' => {
+                let value = &text[..i];
+                let value_end = value.trim_end().len();
+                return (&text[..value_end], Some(text[value_end..].to_string()));
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    (text, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigFormat;
+
+    /// Applies `change` to the parsed `content` and edits the document to match.
+    fn edit(content: &str, change: impl FnOnce(&mut Value)) -> Result<String, String> {
+        let mut target: Value = serde_yaml::from_str(content).unwrap();
+        change(&mut target);
+        let updated = apply(content, &target)?;
+        let parsed: Value = serde_yaml::from_str(&updated).map_err(|e| e.to_string())?;
+        assert_eq!(parsed, target, "{}", updated);
+        Ok(updated)
+    }
+
+    #[test]
+    fn keeps_comments_around_changed_values() {
+        let content = "\
+# gitpower config
+version: 1
+repositories:
+  # the main app
+  - name: app
+    path: ~/src/app # moved last week
+    branch: main
+";
+        let updated = edit(content, |doc| {
+            doc["repositories"][0]["path"] = "~/work/app".into();
+        })
+        .unwrap();
+        assert_eq!(
+            updated,
+            content.replace("~/src/app # moved", "~/work/app # moved")
+        );
+    }
+
+    #[test]
+    fn removes_items_with_their_comments() {
+        let content = "\
+repositories:
+  - name: a
+    path: /a
+  # no longer used
+  - name: b
+    path: /b
+";
+        let updated = edit(content, |doc| {
+            doc["repositories"].as_sequence_mut().unwrap().remove(1);
+        })
+        .unwrap();
+        assert_eq!(updated, "repositories:\n  - name: a\n    path: /a\n");
+    }
+
+    #[test]
+    fn appends_items_at_the_document_indentation() {
+        let content = "repositories:\n- name: a\n  path: /a\n";
+        let updated = edit(content, |doc| {
+            let mut item = Mapping::new();
+            item.insert("name".into(), "b".into());
+            item.insert("path".into(), "/b".into());
+            doc["repositories"]
+                .as_sequence_mut()
+                .unwrap()
+                .push(item.into());
+        })
+        .unwrap();
+        assert_eq!(
+            updated,
+            "repositories:\n- name: a\n  path: /a\n- name: b\n  path: /b\n"
+        );
+    }
+
+    #[test]
+    fn rewrites_changed_flow_sequences_only() {
+        let content = "\
+repositories:
+  - name: app # frontend
+    path: /app
+    groups: [web, prod]
+";
+        let updated = edit(content, |doc| {
+            doc["repositories"][0]["groups"]
+                .as_sequence_mut()
+                .unwrap()
+                .push("mobile".into());
+        })
+        .unwrap();
+        assert_eq!(
+            updated,
+            "\
+repositories:
+  - name: app # frontend
+    path: /app
+    groups:
+      - web
+      - prod
+      - mobile
+"
+        );
+    }
+
+    #[test]
+    fn matches_quoted_keys() {
+        let content = "\"version\": 1\n'defaults':\n  timeout: 30 # seconds\n";
+        let updated = edit(content, |doc| {
+            doc["defaults"]["timeout"] = 60.into();
+        })
+        .unwrap();
+        assert_eq!(
+            updated,
+            "\"version\": 1\n'defaults':\n  timeout: 60 # seconds\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_document_marker() {
+        let content = "---\n# settings\nversion: 1\n";
+        let updated = edit(content, |doc| {
+            doc["version"] = 2.into();
+        })
+        .unwrap();
+        assert_eq!(updated, "---\n# settings\nversion: 2\n");
+    }
+
+    #[test]
+    fn refuses_multiple_documents() {
+        let content = "version: 1\n---\nversion: 2\n";
+        assert!(apply(content, &serde_yaml::from_str("version: 3").unwrap()).is_err());
+    }
+
+    #[test]
+    fn keeps_anchors_of_unchanged_values() {
+        let content = "\
+defaults: &defaults
+  timeout: 30
+repositories:
+  - name: a
+    path: /a
+";
+        let updated = edit(content, |doc| {
+            doc["repositories"][0]["path"] = "/b".into();
+        })
+        .unwrap();
+        assert_eq!(updated, content.replace("/a", "/b"));
+    }
+
+    #[test]
+    fn refuses_edits_that_change_an_alias() {
+        let content = "base: &base\n  timeout: 30\nother: *base\n";
+        let mut target: Value = serde_yaml::from_str(content).unwrap();
+        target["base"]["timeout"] = 60.into();
+
+        // Rewriting `base` drops the anchor `other` refers to; the document
+        // must not be handed back, so the caller falls back to a full rewrite
+        assert!(super::super::apply(ConfigFormat::Yaml, content, &target).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use colored::*;
+use std::error::Error;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for another gitpower process to finish writing the config.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exclusive advisory lock guarding a read-modify-write of the config file.
+///
+/// The lock is taken on a sidecar `<config>.lock` file rather than the config
+/// itself, because saving replaces the config through a rename. It is released
+/// when the guard is dropped.
+pub struct ConfigLock {
+    _file: File,
+}
+
+impl ConfigLock {
+    pub fn acquire(config_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = lock_path(config_path);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        let started = Instant::now();
+        let mut waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < LOCK_TIMEOUT => {
+                    if !waiting {
+                        eprintln!(
+                            "{}: Waiting for another gitpower process to release {}",
+                            "Note".yellow(),
+                            path.display()
+                        );
+                        waiting = true;
+                    }
+                    thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(format!(
+                        "Timed out after {}s waiting for the config lock {}",
+                        LOCK_TIMEOUT.as_secs(),
+                        path.display()
+                    )
+                    .into());
+                }
+                Err(TryLockError::Error(e)) => return Err(Box::new(e)),
+            }
+        }
+    }
+}
+
+fn lock_path(config_path: &Path) -> PathBuf {
+    let mut path = config_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
@@ -0,0 +1,251 @@
+//! Three-way merge of config documents.
+//!
+//! When the file changed on disk between loading and saving, our edits (the
+//! difference between `base` and `ours`) are replayed on top of the current
+//! file instead of overwriting it. Lists of named entries such as repositories
+//! and groups merge entry by entry, plain lists merge as sets, so two processes
+//! adding different repositories never drop each other's work.
+
+use serde_yaml::{Mapping, Value};
+
+/// Merges our changes into `current`, or returns the paths that were changed on both sides.
+pub fn merge(current: &Value, base: &Value, ours: &Value) -> Result<Value, Vec<String>> {
+    let mut conflicts = Vec::new();
+    let merged = merge_value(current, base, ours, "", &mut conflicts);
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(conflicts)
+    }
+}
+
+fn merge_value(
+    current: &Value,
+    base: &Value,
+    ours: &Value,
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Value {
+    if base == ours || current == ours {
+        return current.clone();
+    }
+    if base == current {
+        return ours.clone();
+    }
+
+    match (current, base, ours) {
+        (Value::Mapping(current), Value::Mapping(base), Value::Mapping(ours)) => {
+            Value::Mapping(merge_mapping(current, base, ours, path, conflicts))
+        }
+        (Value::Sequence(current), Value::Sequence(base), Value::Sequence(ours))
+            if [current, base, ours].iter().all(|seq| is_named(seq)) =>
+        {
+            Value::Sequence(merge_named(current, base, ours, path, conflicts))
+        }
+        (Value::Sequence(current), Value::Sequence(base), Value::Sequence(ours)) => {
+            Value::Sequence(merge_set(current, base, ours))
+        }
+        _ => {
+            conflicts.push(display_path(path));
+            current.clone()
+        }
+    }
+}
+
+fn merge_mapping(
+    current: &Mapping,
+    base: &Mapping,
+    ours: &Mapping,
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Mapping {
+    let mut merged = current.clone();
+    let mut keys: Vec<Value> = Vec::new();
+    for key in base.keys().chain(ours.keys()) {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+
+    for key in keys {
+        let child = format!("{}.{}", path, key.as_str().unwrap_or("?"));
+        let (b, o, c) = (base.get(&key), ours.get(&key), current.get(&key));
+        if o == b || o == c {
+            continue;
+        }
+        if b == c {
+            match o {
+                Some(o) => merged.insert(key, o.clone()),
+                None => merged.remove(&key),
+            };
+            continue;
+        }
+        match (b, o, c) {
+            (Some(b), Some(o), Some(c)) => {
+                let value = merge_value(c, b, o, &child, conflicts);
+                merged.insert(key, value);
+            }
+            _ => conflicts.push(display_path(&child)),
+        }
+    }
+
+    merged
+}
+
+fn merge_named(
+    current: &[Value],
+    base: &[Value],
+    ours: &[Value],
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Vec<Value> {
+    let find = |items: &'_ [Value], name: &str| -> Option<usize> {
+        items
+            .iter()
+            .position(|item| item["name"].as_str() == Some(name))
+    };
+
+    let mut merged = current.to_vec();
+    let mut names: Vec<&str> = Vec::new();
+    for name in base
+        .iter()
+        .chain(ours)
+        .filter_map(|item| item["name"].as_str())
+    {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    for name in names {
+        let child = format!("{}[{}]", path, name);
+        let b = find(base, name).map(|i| &base[i]);
+        let o = find(ours, name).map(|i| &ours[i]);
+        let c = find(&merged, name);
+        let c_value = c.map(|i| merged[i].clone());
+
+        if o == b || o == c_value.as_ref() {
+            continue;
+        }
+        match (b, o, c) {
+            (_, Some(o), None) if b.is_none() => merged.push(o.clone()),
+            (Some(b), None, Some(i)) if Some(b) == c_value.as_ref() => {
+                merged.remove(i);
+            }
+            (Some(b), Some(o), Some(i)) => {
+                merged[i] = merge_value(&merged[i].clone(), b, o, &child, conflicts);
+            }
+            _ => conflicts.push(display_path(&child)),
+        }
+    }
+
+    merged
+}
+
+fn merge_set(current: &[Value], base: &[Value], ours: &[Value]) -> Vec<Value> {
+    let mut merged: Vec<Value> = current
+        .iter()
+        .filter(|item| ours.contains(item) || !base.contains(item))
+        .cloned()
+        .collect();
+    for item in ours {
+        if !base.contains(item) && !merged.contains(item) {
+            merged.push(item.clone());
+        }
+    }
+    merged
+}
+
+fn is_named(items: &[Value]) -> bool {
+    items.iter().all(|item| item["name"].is_string())
+}
+
+fn display_path(path: &str) -> String {
+    path.trim_start_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const BASE: &str = "
+defaults:
+  timeout: 30
+repositories:
+- name: a
+  path: /a
+  tags: [rust]
+- name: b
+  path: /b
+";
+
+    #[test]
+    fn keeps_changes_made_on_either_side() {
+        let current = doc(&BASE.replace("timeout: 30", "timeout: 60"));
+        let ours = doc(&BASE.replace("path: /b", "path: /src/b"));
+
+        let merged = merge(&current, &doc(BASE), &ours).unwrap();
+        assert_eq!(
+            merged,
+            doc(&BASE
+                .replace("timeout: 30", "timeout: 60")
+                .replace("path: /b", "path: /src/b"))
+        );
+    }
+
+    #[test]
+    fn merges_named_entries_by_name() {
+        let current = doc(&format!("{}- name: c\n  path: /c\n", BASE));
+        let mut ours = doc(BASE);
+        let repositories = ours["repositories"].as_sequence_mut().unwrap();
+        repositories.remove(0);
+        repositories.push(doc("name: d\npath: /d"));
+
+        let merged = merge(&current, &doc(BASE), &ours).unwrap();
+        let names: Vec<&str> = merged["repositories"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|repo| repo["name"].as_str())
+            .collect();
+        assert_eq!(names, ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn merges_plain_lists_as_sets() {
+        let current = doc(&BASE.replace("[rust]", "[rust, cli]"));
+        let ours = doc(&BASE.replace("[rust]", "[web]"));
+
+        let merged = merge(&current, &doc(BASE), &ours).unwrap();
+        assert_eq!(merged["repositories"][0]["tags"], doc("[cli, web]"));
+    }
+
+    #[test]
+    fn reports_values_changed_on_both_sides() {
+        let current = doc(&BASE.replace("path: /a", "path: /theirs"));
+        let ours = doc(&BASE
+            .replace("path: /a", "path: /ours")
+            .replace("timeout: 30", "timeout: 10"));
+
+        assert_eq!(
+            merge(&current, &doc(BASE), &ours),
+            Err(vec!["repositories[a].path".to_string()])
+        );
+    }
+
+    #[test]
+    fn reports_entries_removed_on_one_side_and_changed_on_the_other() {
+        let current = doc(&BASE.replace("path: /b", "path: /theirs"));
+        let mut ours = doc(BASE);
+        ours["repositories"].as_sequence_mut().unwrap().remove(1);
+
+        assert_eq!(
+            merge(&current, &doc(BASE), &ours),
+            Err(vec!["repositories[b]".to_string()])
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use serde_yaml::{Mapping, Value};
+
+/// Schema version written by this build of gitpower.
+pub const CURRENT_VERSION: u32 = 1;
+
+type Migration = fn(&mut Mapping);
+
+// Indexed by the version each step upgrades *from*: MIGRATIONS[0] turns a
+// v0 document into v1, MIGRATIONS[1] would turn v1 into v2, and so on.
+const MIGRATIONS: &[Migration] = &[v0_to_v1];
+
+/// Reads the `version` key of a raw config document. Files written before
+/// versioning existed have no key and are treated as version 0.
+pub fn detect_version(doc: &Value) -> Result<u32, String> {
+    match doc.get("version") {
+        None | Some(Value::Null) => Ok(0),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("Invalid config version: {}", n)),
+        Some(other) => Err(format!("Invalid config version: {:?}", other)),
+    }
+}
+
+/// Upgrades a raw config document to `CURRENT_VERSION` in place and returns
+/// the version it was at before.
+pub fn migrate(doc: &mut Value) -> Result<u32, String> {
+    let from = detect_version(doc)?;
+
+    if from > CURRENT_VERSION {
+        return Err(format!(
+            "Config version {} is newer than supported version {}; please upgrade gitpower",
+            from, CURRENT_VERSION
+        ));
+    }
+
+    let mapping = doc
+        .as_mapping_mut()
+        .ok_or("Config must be a mapping at the top level")?;
+
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(mapping);
+    }
+
+    // Keep `version` as the first key so it stays visible at the top of the file
+    if from < CURRENT_VERSION {
+        let mut upgraded = Mapping::new();
+        upgraded.insert("version".into(), CURRENT_VERSION.into());
+        for (key, value) in std::mem::take(mapping) {
+            if key.as_str() != Some("version") {
+                upgraded.insert(key, value);
+            }
+        }
+        *mapping = upgraded;
+    }
+
+    Ok(from)
+}
+
+// v0 is the unversioned layout. v1 only introduced the `version` key, so there
+// is nothing to rewrite beyond dropping an explicit `version: null`.
+fn v0_to_v1(doc: &mut Mapping) {
+    doc.remove("version");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn unversioned_documents_are_version_zero() {
+        assert_eq!(detect_version(&doc("repositories: []")), Ok(0));
+        assert_eq!(detect_version(&doc("version: null")), Ok(0));
+        assert_eq!(detect_version(&doc("version: 1")), Ok(1));
+        assert!(detect_version(&doc("version: one")).is_err());
+        assert!(detect_version(&doc("version: -1")).is_err());
+    }
+
+    #[test]
+    fn upgrades_put_the_version_first() {
+        let mut upgraded = doc("repositories: []\nversion: null\ngroups: []");
+        assert_eq!(migrate(&mut upgraded), Ok(0));
+        assert_eq!(upgraded, doc("version: 1\nrepositories: []\ngroups: []"));
+        let keys: Vec<&str> = upgraded
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .filter_map(Value::as_str)
+            .collect();
+        assert_eq!(keys, ["version", "repositories", "groups"]);
+    }
+
+    #[test]
+    fn current_documents_are_left_alone() {
+        let original = doc("repositories: []\nversion: 1");
+        let mut current = original.clone();
+        assert_eq!(migrate(&mut current), Ok(CURRENT_VERSION));
+        assert_eq!(current, original);
+    }
+
+    #[test]
+    fn newer_and_malformed_documents_are_refused() {
+        let error = migrate(&mut doc("version: 99")).unwrap_err();
+        assert!(error.contains("upgrade gitpower"), "{}", error);
+        assert!(migrate(&mut doc("- not a mapping")).is_err());
+    }
+}
@@ -0,0 +1,797 @@
+mod edit;
+pub mod format;
+mod lock;
+mod merge;
+pub mod migrate;
+mod select;
+
+use colored::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub use format::ConfigFormat;
+pub use lock::ConfigLock;
+pub use migrate::CURRENT_VERSION;
+pub use select::{Selection, TagFilter, select_repositories};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Config {
+    /// Schema version of this file; older versions are upgraded on load
+    #[serde(default)]
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<Defaults>,
+    pub repositories: Vec<Repository>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<Group>>,
+    /// Named `run` commands, invoked with `gitpower do <name>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commands: Option<Vec<NamedCommand>>,
+    /// Multi-step workflows, invoked with `gitpower do <name>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workflows: Option<Vec<Workflow>>,
+    /// Hooks for every repository; group and repository hooks run after these
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+    /// What external `gitpower-<name>` plugins get from gitpower
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<Plugin>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq, Clone)]
+pub struct Repository {
+    pub name: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<String>>,
+    /// Free-form labels such as `lang:rust` or `team:payments`, used with `--tag`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Seconds before git or `run` commands in this repository are killed; overrides `defaults.timeout`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// `GIT_SSH_COMMAND` for this repository; overrides `defaults.ssh_command`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssh_command: Option<String>,
+    /// Hooks for this repository, run after the global and group ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+}
+
+/// Settings applied to every repository unless the repository overrides them.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct Defaults {
+    /// Seconds before git or `run` commands are killed; no limit when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// `GIT_SSH_COMMAND` for git operations that talk to a remote. Defaults to
+    /// `ssh -o BatchMode=yes`; set to an empty string to use git's own setting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssh_command: Option<String>,
+    /// How often fetch, pull and push are retried after a transient network error (default 2)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Branches `branch prune` never deletes, besides each repository's default
+    /// branch. A trailing `*` matches any suffix (default: main, master, develop)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protected_branches: Option<Vec<String>>,
+}
+
+/// Shell commands run in the repository directory around gitpower operations.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq, Clone, Default)]
+pub struct Hooks {
+    /// Before pulling; if it fails the repository is not pulled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_pull: Option<String>,
+    /// After a successful pull
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_pull: Option<String>,
+    /// After `gitpower clone` cloned the repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_clone: Option<String>,
+    /// Before syncing; if it fails the repository is not synced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_sync: Option<String>,
+    /// After a successful sync
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_sync: Option<String>,
+    /// Before pushing; if it fails nothing is pushed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_push: Option<String>,
+}
+
+/// The points in an operation where hooks run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PrePull,
+    PostPull,
+    PostClone,
+    PreSync,
+    PostSync,
+    PrePush,
+}
+
+impl Hook {
+    /// Key in the `hooks` section, e.g. `pre_pull`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PrePull => "pre_pull",
+            Hook::PostPull => "post_pull",
+            Hook::PostClone => "post_clone",
+            Hook::PreSync => "pre_sync",
+            Hook::PostSync => "post_sync",
+            Hook::PrePush => "pre_push",
+        }
+    }
+}
+
+impl Hooks {
+    pub fn get(&self, hook: Hook) -> Option<&str> {
+        match hook {
+            Hook::PrePull => self.pre_pull.as_deref(),
+            Hook::PostPull => self.post_pull.as_deref(),
+            Hook::PostClone => self.post_clone.as_deref(),
+            Hook::PreSync => self.pre_sync.as_deref(),
+            Hook::PostSync => self.post_sync.as_deref(),
+            Hook::PrePush => self.pre_push.as_deref(),
+        }
+    }
+}
+
+/// Retries for network operations when neither `--retries` nor `defaults.retries` is set.
+const DEFAULT_RETRIES: u32 = 2;
+
+/// Protected branches when `defaults.protected_branches` is not set.
+const DEFAULT_PROTECTED_BRANCHES: [&str; 3] = ["main", "master", "develop"];
+
+impl Config {
+    /// Time limit for commands in `repo`, falling back to `defaults.timeout`.
+    pub fn timeout_for(&self, repo: &Repository) -> Option<Duration> {
+        match repo.timeout {
+            Some(secs) => (secs > 0).then(|| Duration::from_secs(secs)),
+            None => self.default_timeout(),
+        }
+    }
+
+    /// Time limit for commands in repositories that do not set their own.
+    pub fn default_timeout(&self) -> Option<Duration> {
+        self.defaults
+            .as_ref()?
+            .timeout
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    /// Retries for network operations, preferring `cli` (from `--retries`) over `defaults.retries`.
+    pub fn retries(&self, cli: Option<u32>) -> u32 {
+        cli.or_else(|| self.defaults.as_ref()?.retries)
+            .unwrap_or(DEFAULT_RETRIES)
+    }
+
+    /// Whether `branch prune` must leave `branch` alone in `repo`: its default
+    /// branch and those matching `defaults.protected_branches`.
+    pub fn is_protected_branch(&self, repo: &Repository, branch: &str) -> bool {
+        if repo.branch.as_deref().unwrap_or("main") == branch {
+            return true;
+        }
+        let matches = |pattern: &str| match pattern.strip_suffix('*') {
+            Some(prefix) => branch.starts_with(prefix),
+            None => pattern == branch,
+        };
+        match self
+            .defaults
+            .as_ref()
+            .and_then(|d| d.protected_branches.as_ref())
+        {
+            Some(patterns) => patterns.iter().any(|p| matches(p)),
+            None => DEFAULT_PROTECTED_BRANCHES.into_iter().any(matches),
+        }
+    }
+
+    /// Groups `repo` belongs to, from its own `groups` list and the top-level `groups` section.
+    pub fn groups_of<'a>(&'a self, repo: &'a Repository) -> Vec<&'a str> {
+        let mut groups: Vec<&str> = repo.groups.iter().flatten().map(String::as_str).collect();
+        for group in self.groups.iter().flatten() {
+            if group.repositories.contains(&repo.name) && !groups.contains(&group.name.as_str()) {
+                groups.push(&group.name);
+            }
+        }
+        groups
+    }
+
+    /// Commands to run for `hook` in `repo`: global first, then the groups it
+    /// belongs to, then its own.
+    pub fn hooks_for<'a>(&'a self, repo: &'a Repository, hook: Hook) -> Vec<&'a str> {
+        let groups = self.groups_of(repo);
+        let group_hooks = self
+            .groups
+            .iter()
+            .flatten()
+            .filter(|group| groups.contains(&group.name.as_str()))
+            .filter_map(|group| group.hooks.as_ref());
+
+        self.hooks
+            .iter()
+            .chain(group_hooks)
+            .chain(repo.hooks.as_ref())
+            .filter_map(|hooks| hooks.get(hook))
+            .collect()
+    }
+
+    pub fn command(&self, name: &str) -> Option<&NamedCommand> {
+        self.commands
+            .iter()
+            .flatten()
+            .find(|command| command.name == name)
+    }
+
+    pub fn plugin(&self, name: &str) -> Option<&Plugin> {
+        self.plugins
+            .iter()
+            .flatten()
+            .find(|plugin| plugin.name == name)
+    }
+
+    pub fn workflow(&self, name: &str) -> Option<&Workflow> {
+        self.workflows
+            .iter()
+            .flatten()
+            .find(|workflow| workflow.name == name)
+    }
+
+    /// SSH command for `repo`, falling back to `defaults.ssh_command`.
+    pub fn ssh_command_for<'a>(&'a self, repo: &'a Repository) -> Option<&'a str> {
+        repo.ssh_command
+            .as_deref()
+            .or_else(|| self.defaults.as_ref()?.ssh_command.as_deref())
+    }
+}
+
+impl Repository {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .as_ref()
+            .is_some_and(|tags| tags.iter().any(|t| t == tag))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(remote) = &self.remote
+            && !remote.starts_with("http://")
+            && !remote.starts_with("https://")
+            && !remote.starts_with("git@")
+        {
+            return Err(format!("Invalid remote URL format: {}", remote));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Group {
+    pub name: String,
+    pub repositories: Vec<String>,
+    /// Hooks for the group's repositories, run after the global ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+}
+
+/// A shell command saved under a name, with the repositories it usually runs in.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct NamedCommand {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Command line, with the same placeholders as `gitpower run`
+    pub run: String,
+    /// Shell that interprets `run`: sh (default), bash, zsh or fish
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Repositories or groups to use when none are given on the command line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repos: Option<Vec<String>>,
+    /// Tag filter to use when no `--tag` is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+/// Opt-ins of an external plugin. Without an entry a plugin gets its arguments
+/// untouched and every repository in `GITPOWER_REPOS`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Plugin {
+    pub name: String,
+    /// Take `--repos`, `--tag`/`-t` and `--not-tag` out of the plugin's
+    /// arguments and pass only the repositories they select
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection: Option<bool>,
+    /// Write the config path and selected repositories as JSON to its stdin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<bool>,
+}
+
+/// Steps run one after another in every selected repository.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Workflow {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Repositories or groups to use when none are given on the command line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repos: Option<Vec<String>>,
+    /// Tag filter to use when no `--tag` is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    pub steps: Vec<WorkflowStep>,
+}
+
+/// A workflow step: a bare action name, or a mapping with one of `action`,
+/// `checkout`, `run` or `command` and optionally `on_failure`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum WorkflowStep {
+    /// `fetch`, `pull` or `ensure-clean`
+    Action(String),
+    Detailed(StepDefinition),
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct StepDefinition {
+    /// `fetch`, `pull` or `ensure-clean`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// Branch to check out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkout: Option<String>,
+    /// Shell command, with the same placeholders as `gitpower run`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+    /// Name of an entry in `commands`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// What to do in a repository where this step fails (default `stop`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<OnFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnFailure {
+    /// Skip the remaining steps in this repository
+    #[default]
+    Stop,
+    /// Run the remaining steps anyway; the repository still counts as failed
+    Continue,
+    /// Skip the remaining steps and start no further repositories
+    Abort,
+}
+
+/// Candidate file names probed in the default config directory, in order of preference.
+const DEFAULT_CONFIG_FILES: [&str; 4] = ["config.yml", "config.yaml", "config.toml", "config.json"];
+
+/// Returns the first existing config in `~/.config/gitpower`, falling back to `config.yml`.
+pub fn default_config_path() -> String {
+    let dir = shellexpand::tilde("~/.config/gitpower").into_owned();
+    DEFAULT_CONFIG_FILES
+        .iter()
+        .map(|file| format!("{}/{}", dir, file))
+        .find(|path| Path::new(path).exists())
+        .unwrap_or_else(|| format!("{}/{}", dir, DEFAULT_CONFIG_FILES[0]))
+}
+
+pub fn load_config(config_path: &str) -> Result<Config, Box<dyn Error>> {
+    let path = Path::new(config_path);
+    if !path.exists() {
+        eprintln!(
+            "{}: {}",
+            "Config file not found, creating default config".yellow(),
+            config_path
+        );
+        create_default_config(config_path);
+    }
+
+    read_config(path).map_err(|e| format!("cannot load {}: {}", config_path, e).into())
+}
+
+/// Reads and parses the config, without creating or rewriting the file when
+/// it is missing or invalid.
+pub fn read_config(config_path: &Path) -> Result<Config, Box<dyn Error>> {
+    let format = ConfigFormat::from_path(config_path)?;
+    let content = fs::read_to_string(config_path)?;
+    match parse_config(&content, format) {
+        Ok(config) => Ok(config),
+        Err(ParseError::Version(e)) => Err(e.into()),
+        Err(ParseError::Syntax(e)) => Err(e),
+    }
+}
+
+enum ParseError {
+    Syntax(Box<dyn Error>),
+    Version(String),
+}
+
+/// Parses a config document, upgrading older schema versions in memory.
+fn parse_config(content: &str, format: ConfigFormat) -> Result<Config, ParseError> {
+    let mut doc: serde_yaml::Value = format.parse(content).map_err(ParseError::Syntax)?;
+    let from = migrate::migrate(&mut doc).map_err(ParseError::Version)?;
+
+    if from < CURRENT_VERSION {
+        eprintln!(
+            "{}: Config uses schema version {}, run 'gitpower config migrate' to upgrade it to version {}",
+            "Note".yellow(),
+            from,
+            CURRENT_VERSION
+        );
+    }
+
+    serde_yaml::from_value(doc).map_err(|e| ParseError::Syntax(Box::new(e)))
+}
+
+/// Writes the config back to `config_path` in the format implied by its extension,
+/// editing the existing file in place so comments and key order are kept.
+///
+/// `base` is the config as it was loaded. If another process changed the file
+/// since then, the changes from `base` to `config` are merged into the file on
+/// disk rather than overwriting it; edits to the same entry on both sides are
+/// reported as a conflict, the file is left alone and `config` is written to
+/// `rejected_path` instead so the change can be reapplied by hand.
+pub fn save_config(
+    base: &Config,
+    config: &Config,
+    config_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let _lock = ConfigLock::acquire(config_path)?;
+
+    let base = serde_yaml::to_value(base)?;
+    let ours = serde_yaml::to_value(config)?;
+
+    let target = match read_current(config_path)? {
+        Some(current) if current != base => {
+            eprintln!(
+                "{}: {} was modified by another process, merging changes",
+                "Note".yellow(),
+                config_path.display()
+            );
+            match merge::merge(&current, &base, &ours) {
+                Ok(merged) => merged,
+                Err(conflicts) => {
+                    let rejected = rejected_path(config_path);
+                    let format = ConfigFormat::from_path(config_path)?;
+                    fs::write(&rejected, format.serialize(config)?)?;
+                    return Err(format!(
+                        "Config changed on disk and conflicts with this update ({}); \
+                         the config was left unchanged and the update written to {}",
+                        conflicts.join(", "),
+                        rejected.display()
+                    )
+                    .into());
+                }
+            }
+        }
+        _ => ours,
+    };
+
+    save_document(&target, config_path)
+}
+
+/// Loads the config currently on disk as a document, normalized the same way
+/// as a `Config` so it can be compared with one.
+fn read_current(config_path: &Path) -> Result<Option<serde_yaml::Value>, Box<dyn Error>> {
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let mut doc: serde_yaml::Value = ConfigFormat::from_path(config_path)?.parse(&content)?;
+    migrate::migrate(&mut doc)?;
+    let config: Config = serde_yaml::from_value(doc)?;
+    Ok(Some(serde_yaml::to_value(config)?))
+}
+
+/// Like `save_config`, for raw documents such as the output of a migration.
+/// Callers are expected to hold the `ConfigLock`.
+pub fn save_document(target: &serde_yaml::Value, config_path: &Path) -> Result<(), Box<dyn Error>> {
+    let format = ConfigFormat::from_path(config_path)?;
+
+    let content = match fs::read_to_string(config_path) {
+        Ok(existing) => match edit::apply(format, &existing, target) {
+            Ok(updated) => updated,
+            Err(e) => {
+                eprintln!(
+                    "{}: Could not edit {} in place ({}), rewriting it",
+                    "Warning".yellow(),
+                    config_path.display(),
+                    e
+                );
+                format.serialize(target)?
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => format.serialize(target)?,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    write_config_file(config_path, &content)
+}
+
+/// Replaces the config file atomically: the new content goes to a temporary
+/// file that is renamed over the original, after backing the original up.
+pub fn write_config_file(config_path: &Path, content: &str) -> Result<(), Box<dyn Error>> {
+    if fs::read_to_string(config_path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+
+    let mut tmp_path = config_path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let result = (|| -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        if config_path.exists() {
+            fs::copy(config_path, backup_path(config_path))?;
+        }
+        fs::rename(&tmp_path, config_path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    Ok(result?)
+}
+
+/// Location of the backup written next to the config before it is rewritten.
+pub fn backup_path(config_path: &Path) -> PathBuf {
+    let mut path = config_path.as_os_str().to_owned();
+    path.push(".bak");
+    PathBuf::from(path)
+}
+
+/// Where `save_config` writes an update that conflicts with the file on disk,
+/// e.g. `config.rejected.yml`.
+fn rejected_path(config_path: &Path) -> PathBuf {
+    let stem = config_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let mut name = format!("{}.rejected", stem);
+    if let Some(extension) = config_path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    config_path.with_file_name(name)
+}
+
+pub fn create_default_config(config_path: &str) {
+    let path = Path::new(config_path);
+
+    // Create directory if it doesn't exist
+    if let Some(parent) = path.parent()
+        && !parent.exists()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        eprintln!("Failed to create config directory: {}", e);
+        return;
+    }
+
+    let default_config = Config {
+        version: CURRENT_VERSION,
+        defaults: None,
+        repositories: vec![Repository {
+            name: "example-repo".to_string(),
+            path: "~/repos/example".to_string(),
+            remote: Some("origin".to_string()),
+            branch: Some("main".to_string()),
+            groups: Some(vec!["default".to_string()]),
+            tags: None,
+            timeout: None,
+            ssh_command: None,
+            hooks: None,
+        }],
+        groups: Some(vec![Group {
+            name: "default".to_string(),
+            repositories: vec!["example-repo".to_string()],
+            hooks: None,
+        }]),
+        commands: None,
+        workflows: None,
+        plugins: None,
+        hooks: None,
+    };
+
+    let format = match ConfigFormat::from_path(path) {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("Failed to create default config: {}", e);
+            return;
+        }
+    };
+
+    match format.serialize(&default_config) {
+        Ok(content) => {
+            // Never replace a config that appeared in the meantime
+            let written = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(content.as_bytes()));
+            if let Err(e) = written {
+                eprintln!("Failed to write default config: {}", e);
+            } else {
+                println!("Created default config at {}", config_path);
+                println!("Please edit this file to add your repositories.");
+            }
+        }
+        Err(e) => eprintln!("Failed to serialize default config: {}", e),
+    }
+}
+
+pub fn get_repositories_by_names<'a>(config: &'a Config, names: &[&str]) -> Vec<&'a Repository> {
+    if names.is_empty() {
+        return config.repositories.iter().collect();
+    }
+
+    let mut result = Vec::new();
+    let empty_groups = Vec::new();
+    let groups = config.groups.as_ref().unwrap_or(&empty_groups);
+
+    for name in names {
+        // Check if name is a group
+        let group_repos: Vec<&str> = groups
+            .iter()
+            .filter(|g| g.name == *name)
+            .flat_map(|g| g.repositories.iter().map(|r| r.as_str()))
+            .collect();
+
+        if !group_repos.is_empty() {
+            // It's a group, add all repositories in this group
+            for repo_name in group_repos {
+                if let Some(repo) = config.repositories.iter().find(|r| r.name == repo_name) {
+                    if !result.contains(&repo) {
+                        result.push(repo);
+                    }
+                } else {
+                    eprintln!(
+                        "{}: Repository '{}' in group '{}' not found",
+                        "Warning".yellow(),
+                        repo_name,
+                        name
+                    );
+                }
+            }
+        } else {
+            // It's a repository name
+            if let Some(repo) = config.repositories.iter().find(|r| r.name == *name) {
+                if !result.contains(&repo) {
+                    result.push(repo);
+                }
+            } else {
+                eprintln!(
+                    "{}: Repository or group '{}' not found",
+                    "Warning".yellow(),
+                    name
+                );
+            }
+        }
+    }
+
+    if result.is_empty() {
+        eprintln!(
+            "{}: No valid repositories found for the specified names",
+            "Warning".yellow()
+        );
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ScratchDir, repository};
+
+    #[test]
+    fn load_leaves_an_invalid_config_alone() {
+        let dir = ScratchDir::new("invalid");
+        let path = dir.join("config.yml");
+        fs::write(&path, "repositories: [\n").unwrap();
+        assert!(load_config(path.to_str().unwrap()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "repositories: [\n");
+    }
+
+    #[test]
+    fn load_creates_a_missing_config() {
+        let dir = ScratchDir::new("missing");
+        let path = dir.join("config.toml");
+        let config = load_config(path.to_str().unwrap()).unwrap();
+        assert_eq!(config.repositories[0].name, "example-repo");
+        assert_eq!(read_config(&path).unwrap().version, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn save_merges_entries_added_by_another_process() {
+        let dir = ScratchDir::new("merge");
+        let path = dir.join("config.yml");
+        fs::write(
+            &path,
+            "version: 1\nrepositories:\n- name: a\n  path: /src/a\n",
+        )
+        .unwrap();
+        let base = read_config(&path).unwrap();
+
+        let mut theirs = base.clone();
+        theirs
+            .repositories
+            .push(repository("b", Path::new("/src/b")));
+        save_config(&base, &theirs, &path).unwrap();
+
+        let mut ours = base.clone();
+        ours.repositories.push(repository("c", Path::new("/src/c")));
+        save_config(&base, &ours, &path).unwrap();
+
+        let names: Vec<String> = read_config(&path)
+            .unwrap()
+            .repositories
+            .into_iter()
+            .map(|repo| repo.name)
+            .collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn save_keeps_conflicting_update_recoverable() {
+        let dir = ScratchDir::new("conflict");
+        let path = dir.join("config.yml");
+        fs::write(
+            &path,
+            "version: 1\nrepositories:\n- name: a\n  path: /src/a\n",
+        )
+        .unwrap();
+        let base = read_config(&path).unwrap();
+
+        let mut theirs = base.clone();
+        theirs.repositories[0].path = "/theirs".to_string();
+        save_config(&base, &theirs, &path).unwrap();
+
+        let mut ours = base.clone();
+        ours.repositories[0].path = "/ours".to_string();
+        let error = save_config(&base, &ours, &path).unwrap_err().to_string();
+        assert!(error.contains("repositories"), "{}", error);
+
+        assert_eq!(read_config(&path).unwrap().repositories[0].path, "/theirs");
+        let rejected = read_config(&rejected_path(&path)).unwrap();
+        assert_eq!(rejected.repositories[0].path, "/ours");
+    }
+
+    #[test]
+    fn save_rewrites_documents_it_cannot_edit_in_place() {
+        let dir = ScratchDir::new("rewrite");
+        let path = dir.join("config.yml");
+        fs::write(&path, "defaults: &defaults\n  timeout: 30\nrepositories:\n- name: a\n  path: /src/a\n  <<: *defaults\n").unwrap();
+        let mut target: serde_yaml::Value =
+            serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        target["defaults"]["timeout"] = 60.into();
+
+        save_document(&target, &path).unwrap();
+
+        let saved: serde_yaml::Value =
+            serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved, target);
+    }
+
+    #[test]
+    fn rejected_path_keeps_the_extension() {
+        assert_eq!(
+            rejected_path(Path::new("/etc/gitpower/config.toml")),
+            Path::new("/etc/gitpower/config.rejected.toml")
+        );
+    }
+}
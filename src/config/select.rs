@@ -0,0 +1,148 @@
+use colored::*;
+
+use super::{Config, Repository, get_repositories_by_names};
+
+/// Tag constraints applied on top of name/group selection.
+///
+/// Every entry of `all` must match; an entry lists alternatives of which at
+/// least one tag must be present (`--tag a,b` means a OR b). Repositories
+/// carrying any tag from `none` are excluded.
+#[derive(Debug, Default, Clone)]
+pub struct TagFilter {
+    pub all: Vec<Vec<String>>,
+    pub none: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn new<'a>(
+        tags: impl IntoIterator<Item = &'a str>,
+        not_tags: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let split = |value: &str| -> Vec<String> {
+            value
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect()
+        };
+
+        Self {
+            all: tags
+                .into_iter()
+                .map(split)
+                .filter(|any| !any.is_empty())
+                .collect(),
+            none: not_tags.into_iter().flat_map(split).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.all.is_empty() && self.none.is_empty()
+    }
+
+    pub fn matches(&self, repo: &Repository) -> bool {
+        let has = |tag: &String| repo.has_tag(tag);
+        self.all.iter().all(|any| any.iter().any(has)) && !self.none.iter().any(has)
+    }
+}
+
+/// Which repositories a command should act on: names or groups, narrowed down by tags.
+#[derive(Debug, Default, Clone)]
+pub struct Selection<'a> {
+    pub names: Vec<&'a str>,
+    pub tags: TagFilter,
+}
+
+pub fn select_repositories<'a>(config: &'a Config, selection: &Selection) -> Vec<&'a Repository> {
+    let repos = get_repositories_by_names(config, &selection.names);
+    if selection.tags.is_empty() || repos.is_empty() {
+        return repos;
+    }
+
+    let selected: Vec<&Repository> = repos
+        .into_iter()
+        .filter(|repo| selection.tags.matches(repo))
+        .collect();
+
+    if selected.is_empty() {
+        eprintln!(
+            "{}: No repositories match the tag filter",
+            "Warning".yellow()
+        );
+    }
+
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        serde_yaml::from_str(
+            "
+repositories:
+- name: api
+  path: /api
+  tags: [lang:rust, team:payments]
+- name: web
+  path: /web
+  tags: [lang:ts, team:payments]
+- name: cli
+  path: /cli
+  tags: [lang:rust, archived]
+- name: docs
+  path: /docs
+groups:
+- name: backend
+  repositories: [api, cli]
+",
+        )
+        .unwrap()
+    }
+
+    fn names(tags: &[&str], not_tags: &[&str], names: &[&str]) -> Vec<String> {
+        let config = config();
+        let selection = Selection {
+            names: names.to_vec(),
+            tags: TagFilter::new(tags.iter().copied(), not_tags.iter().copied()),
+        };
+        select_repositories(&config, &selection)
+            .into_iter()
+            .map(|repo| repo.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn splits_tag_lists_and_drops_empty_entries() {
+        let filter = TagFilter::new(["a, b", ",", "c"], ["x,,y"]);
+        assert_eq!(filter.all, [vec!["a", "b"], vec!["c"]]);
+        assert_eq!(filter.none, ["x", "y"]);
+        assert!(TagFilter::new([""], [" "]).is_empty());
+    }
+
+    #[test]
+    fn comma_means_or_and_repeated_flags_mean_and() {
+        assert_eq!(
+            names(&["lang:rust,lang:ts"], &[], &[]),
+            ["api", "web", "cli"]
+        );
+        assert_eq!(names(&["lang:rust", "team:payments"], &[], &[]), ["api"]);
+    }
+
+    #[test]
+    fn excluded_tags_win() {
+        assert_eq!(names(&["lang:rust"], &["archived"], &[]), ["api"]);
+        assert_eq!(names(&[], &["team:payments"], &[]), ["cli", "docs"]);
+    }
+
+    #[test]
+    fn tags_narrow_down_names_and_groups() {
+        assert_eq!(names(&[], &["archived"], &["backend"]), ["api"]);
+        assert_eq!(names(&["lang:ts"], &[], &["backend"]), Vec::<String>::new());
+        assert_eq!(
+            names(&[], &[], &["docs", "backend"]),
+            ["docs", "api", "cli"]
+        );
+    }
+}
@@ -0,0 +1,406 @@
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+use crate::process::{self, Finished, Stream};
+
+use super::{
+    AheadBehind, Commit, GitBackend, GitOutput, LocalTimeouts, ProgressHandler, Remote, RunOptions,
+    StatusEntry,
+};
+
+/// SSH command used when neither gitpower's config, `GIT_SSH_COMMAND` nor the
+/// repository's `core.sshCommand` names one: fail instead of prompting.
+const BATCH_SSH_COMMAND: &str = "ssh -o BatchMode=yes";
+
+/// Backend that spawns the `git` binary for every operation.
+///
+/// git never prompts: credential and passphrase prompts are disabled and
+/// messages are forced to the C locale so failures can be recognised.
+#[derive(Debug, Default, Clone)]
+pub struct CliGit {
+    timeouts: LocalTimeouts,
+}
+
+impl CliGit {
+    /// Backend whose local operations are stopped after `timeouts`;
+    /// operations that talk to a remote use the timeout of their `RunOptions`.
+    pub fn with_timeouts(timeouts: LocalTimeouts) -> Self {
+        Self { timeouts }
+    }
+
+    fn run(
+        &self,
+        repo: &Path,
+        args: &[&str],
+        timeout: Option<Duration>,
+        ssh_command: Option<&str>,
+        progress: Option<&ProgressHandler>,
+    ) -> Finished {
+        let mut command = Command::new("git");
+        command
+            .current_dir(repo)
+            .args(args)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GCM_INTERACTIVE", "never")
+            .env("LC_ALL", "C");
+        if let Some(ssh_command) = ssh_command {
+            command.env("GIT_SSH_COMMAND", ssh_command);
+        }
+
+        let finished = match progress {
+            Some(progress) => {
+                let on_update = |stream: Stream, update: &str| {
+                    if stream == Stream::Stderr && !update.trim().is_empty() {
+                        progress(update.trim());
+                    }
+                };
+                process::run_progress(command, timeout, &on_update)
+            }
+            None => process::run(command, timeout),
+        };
+
+        finished.unwrap_or_else(|e| Finished {
+            status: ExitStatus::from_raw(1),
+            stdout: Vec::new(),
+            stderr: format!("Failed to run git: {}", e).into_bytes(),
+            interrupted: None,
+        })
+    }
+
+    fn run_output(&self, repo: &Path, args: &[&str]) -> GitOutput {
+        let timeout = self.timeouts.for_repo(repo);
+        to_output(self.run(repo, args, timeout, None, None))
+    }
+
+    /// Runs an operation that talks to a remote, which may need SSH.
+    fn run_remote(&self, repo: &Path, args: &[&str], options: &RunOptions) -> GitOutput {
+        let ssh_command = match options.ssh_command.as_deref() {
+            // An empty command leaves the choice to git
+            Some("") => None,
+            Some(ssh_command) => Some(ssh_command),
+            None => self.uses_default_ssh(repo).then_some(BATCH_SSH_COMMAND),
+        };
+
+        let Some(progress) = &options.progress else {
+            return to_output(self.run(repo, args, options.timeout, ssh_command, None));
+        };
+
+        // git only reports progress to a terminal unless asked to
+        let mut args = args.to_vec();
+        args.insert(1, "--progress");
+        let mut output =
+            to_output(self.run(repo, &args, options.timeout, ssh_command, Some(progress)));
+        output.stderr = strip_progress(&output.stderr);
+        output
+    }
+
+    /// Runs a read-only command and returns its stdout if it succeeded.
+    fn query(&self, repo: &Path, args: &[&str]) -> Option<String> {
+        let output = self.run(repo, args, self.timeouts.for_repo(repo), None, None);
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Whether git would fall back to plain `ssh` for this repository.
+    fn uses_default_ssh(&self, repo: &Path) -> bool {
+        std::env::var_os("GIT_SSH_COMMAND").is_none()
+            && std::env::var_os("GIT_SSH").is_none()
+            && self
+                .query(repo, &["config", "--get", "core.sshCommand"])
+                .is_none()
+    }
+}
+
+impl GitBackend for CliGit {
+    fn init(&self, repo: &Path) -> GitOutput {
+        self.run_output(repo, &["init"])
+    }
+
+    fn current_branch(&self, repo: &Path) -> Option<String> {
+        self.query(repo, &["branch", "--show-current"])
+            .map(|out| out.trim().to_string())
+            .filter(|branch| !branch.is_empty())
+    }
+
+    fn status(&self, repo: &Path) -> Result<Vec<StatusEntry>, String> {
+        let output = self.run_output(repo, &["status", "--porcelain"]);
+        if !output.success {
+            return Err(output.stderr.trim().to_string());
+        }
+        Ok(output
+            .stdout
+            .lines()
+            .filter(|line| line.len() > 3)
+            .map(|line| StatusEntry {
+                code: line[0..2].to_string(),
+                path: line[3..].to_string(),
+            })
+            .collect())
+    }
+
+    fn ahead_behind(&self, repo: &Path) -> Option<AheadBehind> {
+        let output = self.query(
+            repo,
+            &["rev-list", "--count", "--left-right", "@{upstream}...HEAD"],
+        )?;
+        let (behind, ahead) = output.trim().split_once('\t')?;
+        Some(AheadBehind {
+            ahead: ahead.parse().ok()?,
+            behind: behind.parse().ok()?,
+        })
+    }
+
+    fn remotes(&self, repo: &Path) -> Vec<Remote> {
+        let output = self.query(repo, &["remote", "-v"]).unwrap_or_default();
+        let mut remotes: Vec<Remote> = Vec::new();
+        for line in output.lines() {
+            let mut parts = line.split_whitespace();
+            if let (Some(name), Some(url)) = (parts.next(), parts.next())
+                && !remotes.iter().any(|r| r.name == name)
+            {
+                remotes.push(Remote {
+                    name: name.to_string(),
+                    url: url.to_string(),
+                });
+            }
+        }
+        remotes
+    }
+
+    fn branches(&self, repo: &Path) -> Vec<String> {
+        self.query(
+            repo,
+            &["for-each-ref", "--format=%(refname:short)", "refs/heads"],
+        )
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+    }
+
+    fn remote_branches(&self, repo: &Path) -> Vec<String> {
+        self.query(
+            repo,
+            &["for-each-ref", "--format=%(refname:short)", "refs/remotes"],
+        )
+        .unwrap_or_default()
+        .lines()
+        // `<remote>/HEAD` is listed as just `<remote>` by newer versions of git
+        .filter(|name| name.contains('/') && !name.ends_with("/HEAD"))
+        .map(str::to_string)
+        .collect()
+    }
+
+    fn add_remote(&self, repo: &Path, name: &str, url: &str) -> GitOutput {
+        self.run_output(repo, &["remote", "add", name, url])
+    }
+
+    fn add_all(&self, repo: &Path) -> GitOutput {
+        self.run_output(repo, &["add", "."])
+    }
+
+    fn commit(&self, repo: &Path, message: &str) -> GitOutput {
+        self.run_output(repo, &["commit", "-m", message])
+    }
+
+    fn commit_tracked(&self, repo: &Path, message: &str) -> GitOutput {
+        self.run_output(repo, &["commit", "-a", "-m", message])
+    }
+
+    fn checkout(&self, repo: &Path, branch: &str) -> GitOutput {
+        self.run_output(repo, &["checkout", branch])
+    }
+
+    fn create_branch(&self, repo: &Path, branch: &str, start: &str, track: bool) -> GitOutput {
+        let track = if track { "--track" } else { "--no-track" };
+        self.run_output(repo, &["checkout", track, "-b", branch, start])
+    }
+
+    fn merged_branches(&self, repo: &Path, target: &str) -> Vec<String> {
+        let merged = format!("--merged={}", target);
+        self.query(
+            repo,
+            &[
+                "for-each-ref",
+                &merged,
+                "--format=%(refname:short)",
+                "refs/heads",
+            ],
+        )
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+    }
+
+    fn unpushed_commits(&self, repo: &Path, branch: &str, base: Option<&str>) -> Vec<Commit> {
+        let mut args = vec!["log", "--format=%H%x09%s", branch, "--not", "--remotes"];
+        args.extend(base);
+        parse_log(self.query(repo, &args).unwrap_or_default())
+    }
+
+    fn gone_branches(&self, repo: &Path) -> Vec<String> {
+        self.query(
+            repo,
+            &[
+                "for-each-ref",
+                "--format=%(refname:short) %(upstream:track)",
+                "refs/heads",
+            ],
+        )
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.strip_suffix(" [gone]"))
+        .map(str::to_string)
+        .collect()
+    }
+
+    fn delete_branch(&self, repo: &Path, branch: &str, force: bool) -> GitOutput {
+        let flag = if force { "-D" } else { "-d" };
+        self.run_output(repo, &["branch", flag, branch])
+    }
+
+    fn stash(&self, repo: &Path, message: &str) -> GitOutput {
+        self.run_output(
+            repo,
+            &["stash", "push", "--include-untracked", "-m", message],
+        )
+    }
+
+    fn stashes(&self, repo: &Path) -> Vec<String> {
+        self.query(repo, &["stash", "list", "--format=%gs"])
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn stash_pop(&self, repo: &Path, index: usize) -> GitOutput {
+        self.run_output(repo, &["stash", "pop", &format!("stash@{{{}}}", index)])
+    }
+
+    fn tags(&self, repo: &Path) -> Vec<String> {
+        self.query(
+            repo,
+            &["for-each-ref", "--format=%(refname:short)", "refs/tags"],
+        )
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+    }
+
+    fn resolve(&self, repo: &Path, rev: &str) -> Option<String> {
+        let commit = format!("{}^{{commit}}", rev);
+        self.query(repo, &["rev-parse", "--verify", "--quiet", &commit])
+            .map(|sha| sha.trim().to_string())
+    }
+
+    fn create_tag(
+        &self,
+        repo: &Path,
+        tag: &str,
+        target: &str,
+        message: &str,
+        sign: bool,
+    ) -> GitOutput {
+        let kind = if sign { "-s" } else { "-a" };
+        self.run_output(repo, &["tag", kind, tag, "-m", message, target])
+    }
+
+    fn delete_tag(&self, repo: &Path, tag: &str) -> GitOutput {
+        self.run_output(repo, &["tag", "-d", tag])
+    }
+
+    fn push_tag(
+        &self,
+        repo: &Path,
+        remote: &str,
+        tag: &str,
+        delete: bool,
+        options: &RunOptions,
+    ) -> GitOutput {
+        let refspec = format!("refs/tags/{}", tag);
+        if delete {
+            self.run_remote(repo, &["push", remote, "--delete", &refspec], options)
+        } else {
+            self.run_remote(repo, &["push", remote, &refspec], options)
+        }
+    }
+
+    fn clone_repo(&self, url: &str, path: &Path, options: &RunOptions) -> GitOutput {
+        // git clone runs from the parent, which may not exist yet either
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            return GitOutput::failed(format!("Failed to create {}: {}", parent.display(), e));
+        }
+        self.run_remote(parent, &["clone", url, &path.to_string_lossy()], options)
+    }
+
+    fn fetch(&self, repo: &Path, remote: &str, options: &RunOptions) -> GitOutput {
+        self.run_remote(repo, &["fetch", remote], options)
+    }
+
+    fn fetch_commit(
+        &self,
+        repo: &Path,
+        remote: &str,
+        sha: &str,
+        options: &RunOptions,
+    ) -> GitOutput {
+        self.run_remote(repo, &["fetch", remote, sha], options)
+    }
+
+    fn pull(&self, repo: &Path, remote: &str, branch: &str, options: &RunOptions) -> GitOutput {
+        self.run_remote(repo, &["pull", remote, branch], options)
+    }
+
+    fn push(&self, repo: &Path, remote: &str, branch: &str, options: &RunOptions) -> GitOutput {
+        self.run_remote(repo, &["push", remote, branch], options)
+    }
+
+    fn log(&self, repo: &Path, limit: usize) -> Vec<Commit> {
+        let limit = format!("-{}", limit);
+        parse_log(
+            self.query(repo, &["log", &limit, "--format=%H%x09%s"])
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Parses `git log --format=%H%x09%s` output.
+fn parse_log(stdout: String) -> Vec<Commit> {
+    stdout
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(sha, summary)| Commit {
+            sha: sha.to_string(),
+            summary: summary.to_string(),
+        })
+        .collect()
+}
+
+fn to_output(output: Finished) -> GitOutput {
+    GitOutput {
+        success: output.status.success() && output.interrupted.is_none(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        interrupted: output.interrupted,
+    }
+}
+
+/// Drops progress meters from captured stderr, keeping git's other messages.
+fn strip_progress(stderr: &str) -> String {
+    stderr
+        .lines()
+        .filter(|line| !line.contains('\r') && !line.contains("% ("))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
@@ -0,0 +1,508 @@
+//! In-memory `GitBackend` for exercising command logic without spawning git.
+//!
+//! Repositories are described up front with `FakeRepo` and evolve as
+//! operations run: committing clears the working tree, pulling catches up with
+//! the upstream, pushing publishes local commits. Every call is recorded so
+//! callers can assert on what a command did.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::{AheadBehind, Commit, GitBackend, GitOutput, Remote, RunOptions, StatusEntry};
+
+#[derive(Debug, Clone, Default)]
+pub struct FakeRepo {
+    pub branch: Option<String>,
+    /// Local branches, including `branch`
+    pub branches: Vec<String>,
+    /// Remote-tracking branches as `<remote>/<branch>`
+    pub remote_branches: Vec<String>,
+    /// Local branches merged into the default branch
+    pub merged: Vec<String>,
+    /// Local branches whose upstream was deleted
+    pub gone: Vec<String>,
+    /// Commits only on a local branch, by branch
+    pub unpushed: HashMap<String, Vec<Commit>>,
+    pub tags: Vec<String>,
+    /// Stash entries as `On <branch>: <message>`, newest first
+    pub stashes: Vec<String>,
+    pub changes: Vec<StatusEntry>,
+    pub remotes: Vec<Remote>,
+    pub upstream: Option<AheadBehind>,
+    pub commits: Vec<Commit>,
+    /// Operations (e.g. `"pull"`) that fail with the given stderr
+    pub failures: HashMap<String, String>,
+}
+
+impl FakeRepo {
+    pub fn new(branch: &str) -> Self {
+        Self {
+            branch: Some(branch.to_string()),
+            branches: vec![branch.to_string()],
+            ..Self::default()
+        }
+    }
+
+    pub fn with_branch(mut self, branch: &str) -> Self {
+        self.branches.push(branch.to_string());
+        self
+    }
+
+    pub fn with_remote_branch(mut self, branch: &str) -> Self {
+        self.remote_branches.push(branch.to_string());
+        self
+    }
+
+    pub fn with_change(mut self, code: &str, path: &str) -> Self {
+        self.changes.push(StatusEntry {
+            code: code.to_string(),
+            path: path.to_string(),
+        });
+        self
+    }
+
+    pub fn with_remote(mut self, name: &str, url: &str) -> Self {
+        self.remotes.push(Remote {
+            name: name.to_string(),
+            url: url.to_string(),
+        });
+        self
+    }
+
+    pub fn with_upstream(mut self, ahead: usize, behind: usize) -> Self {
+        self.upstream = Some(AheadBehind { ahead, behind });
+        self
+    }
+
+    pub fn failing(mut self, operation: &str, stderr: &str) -> Self {
+        self.failures
+            .insert(operation.to_string(), stderr.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FakeGit {
+    repos: Mutex<HashMap<PathBuf, FakeRepo>>,
+    calls: Mutex<Vec<String>>,
+}
+
+impl FakeGit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_repo(self, path: impl Into<PathBuf>, repo: FakeRepo) -> Self {
+        self.repos.lock().unwrap().insert(path.into(), repo);
+        self
+    }
+
+    pub fn repo(&self, path: &Path) -> Option<FakeRepo> {
+        self.repos.lock().unwrap().get(path).cloned()
+    }
+
+    /// Changes a repository behind the commands' back, e.g. editing files.
+    pub fn update(&self, path: &Path, change: impl FnOnce(&mut FakeRepo)) {
+        change(self.repos.lock().unwrap().get_mut(path).unwrap());
+    }
+
+    /// Operations performed so far, formatted as `"<op> <repo path> <args...>"`.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, operation: &str, repo: &Path, args: &[&str]) {
+        let mut call = format!("{} {}", operation, repo.display());
+        for arg in args {
+            call.push(' ');
+            call.push_str(arg);
+        }
+        self.calls.lock().unwrap().push(call);
+    }
+
+    /// Runs `apply` against the repository unless the operation was set up to fail.
+    fn mutate(
+        &self,
+        operation: &str,
+        repo: &Path,
+        args: &[&str],
+        apply: impl FnOnce(&mut FakeRepo) -> GitOutput,
+    ) -> GitOutput {
+        self.record(operation, repo, args);
+        let mut repos = self.repos.lock().unwrap();
+        match repos.get_mut(repo) {
+            Some(state) => match state.failures.get(operation) {
+                Some(stderr) => GitOutput::failed(stderr.clone()),
+                None => apply(state),
+            },
+            None => GitOutput::failed(format!("fatal: not a git repository: {}", repo.display())),
+        }
+    }
+
+    fn read<T>(
+        &self,
+        operation: &str,
+        repo: &Path,
+        read: impl FnOnce(&FakeRepo) -> T,
+    ) -> Option<T> {
+        self.record(operation, repo, &[]);
+        let repos = self.repos.lock().unwrap();
+        repos
+            .get(repo)
+            .filter(|state| !state.failures.contains_key(operation))
+            .map(read)
+    }
+}
+
+impl GitBackend for FakeGit {
+    fn init(&self, repo: &Path) -> GitOutput {
+        self.record("init", repo, &[]);
+        self.repos
+            .lock()
+            .unwrap()
+            .entry(repo.to_path_buf())
+            .or_insert_with(|| FakeRepo::new("main"));
+        GitOutput::ok(format!(
+            "Initialized empty Git repository in {}",
+            repo.display()
+        ))
+    }
+
+    fn current_branch(&self, repo: &Path) -> Option<String> {
+        self.read("branch", repo, |state| state.branch.clone())
+            .flatten()
+    }
+
+    fn status(&self, repo: &Path) -> Result<Vec<StatusEntry>, String> {
+        self.read("status", repo, |state| state.changes.clone())
+            .ok_or_else(|| "fatal: not a git repository".to_string())
+    }
+
+    fn ahead_behind(&self, repo: &Path) -> Option<AheadBehind> {
+        self.read("rev-list", repo, |state| state.upstream)
+            .flatten()
+    }
+
+    fn remotes(&self, repo: &Path) -> Vec<Remote> {
+        self.read("remote", repo, |state| state.remotes.clone())
+            .unwrap_or_default()
+    }
+
+    fn branches(&self, repo: &Path) -> Vec<String> {
+        self.read("branches", repo, |state| state.branches.clone())
+            .unwrap_or_default()
+    }
+
+    fn remote_branches(&self, repo: &Path) -> Vec<String> {
+        self.read("remote_branches", repo, |state| {
+            state.remote_branches.clone()
+        })
+        .unwrap_or_default()
+    }
+
+    fn add_remote(&self, repo: &Path, name: &str, url: &str) -> GitOutput {
+        self.mutate("remote-add", repo, &[name, url], |state| {
+            if state.remotes.iter().any(|r| r.name == name) {
+                return GitOutput::failed(format!("error: remote {} already exists.", name));
+            }
+            state.remotes.push(Remote {
+                name: name.to_string(),
+                url: url.to_string(),
+            });
+            GitOutput::ok("")
+        })
+    }
+
+    fn add_all(&self, repo: &Path) -> GitOutput {
+        self.mutate("add", repo, &["."], |_| GitOutput::ok(""))
+    }
+
+    fn commit(&self, repo: &Path, message: &str) -> GitOutput {
+        self.mutate("commit", repo, &[message], |state| {
+            if state.changes.is_empty() {
+                return nothing_to_commit();
+            }
+            state.changes.clear();
+            add_commit(state, message)
+        })
+    }
+
+    fn commit_tracked(&self, repo: &Path, message: &str) -> GitOutput {
+        self.mutate("commit-tracked", repo, &[message], |state| {
+            if state.changes.iter().all(|change| change.code == "??") {
+                return nothing_to_commit();
+            }
+            state.changes.retain(|change| change.code == "??");
+            add_commit(state, message)
+        })
+    }
+
+    fn checkout(&self, repo: &Path, branch: &str) -> GitOutput {
+        self.mutate("checkout", repo, &[branch], |state| {
+            if state.commits.iter().any(|c| c.sha == branch) {
+                state.branch = None;
+                return GitOutput::ok(format!("HEAD is now at {}", &branch[..7]));
+            }
+            if !state.branches.iter().any(|b| b == branch) {
+                return GitOutput::failed(format!(
+                    "error: pathspec '{}' did not match any file(s) known to git",
+                    branch
+                ));
+            }
+            state.branch = Some(branch.to_string());
+            GitOutput::ok("")
+        })
+    }
+
+    fn create_branch(&self, repo: &Path, branch: &str, start: &str, _track: bool) -> GitOutput {
+        self.mutate("create_branch", repo, &[branch, start], |state| {
+            if state.branches.iter().any(|b| b == branch) {
+                return GitOutput::failed(format!(
+                    "fatal: a branch named '{}' already exists",
+                    branch
+                ));
+            }
+            state.branches.push(branch.to_string());
+            state.branch = Some(branch.to_string());
+            GitOutput::ok("")
+        })
+    }
+
+    fn merged_branches(&self, repo: &Path, _target: &str) -> Vec<String> {
+        self.read("merged_branches", repo, |state| state.merged.clone())
+            .unwrap_or_default()
+    }
+
+    fn unpushed_commits(&self, repo: &Path, branch: &str, _base: Option<&str>) -> Vec<Commit> {
+        self.read("unpushed_commits", repo, |state| {
+            state.unpushed.get(branch).cloned().unwrap_or_default()
+        })
+        .unwrap_or_default()
+    }
+
+    fn gone_branches(&self, repo: &Path) -> Vec<String> {
+        self.read("gone_branches", repo, |state| state.gone.clone())
+            .unwrap_or_default()
+    }
+
+    fn delete_branch(&self, repo: &Path, branch: &str, force: bool) -> GitOutput {
+        let flag = if force { "-D" } else { "-d" };
+        self.mutate("delete_branch", repo, &[flag, branch], |state| {
+            if !state.branches.iter().any(|b| b == branch) {
+                return GitOutput::failed(format!("error: branch '{}' not found", branch));
+            }
+            if !force && !state.merged.iter().any(|b| b == branch) {
+                return GitOutput::failed(format!(
+                    "error: the branch '{}' is not fully merged",
+                    branch
+                ));
+            }
+            state.branches.retain(|b| b != branch);
+            state.merged.retain(|b| b != branch);
+            state.gone.retain(|b| b != branch);
+            state.unpushed.remove(branch);
+            GitOutput::ok(format!("Deleted branch {}", branch))
+        })
+    }
+
+    fn stash(&self, repo: &Path, message: &str) -> GitOutput {
+        self.mutate("stash", repo, &[message], |state| {
+            if state.changes.is_empty() {
+                return GitOutput::ok("No local changes to save");
+            }
+            state.changes.clear();
+            let entry = format!(
+                "On {}: {}",
+                state.branch.as_deref().unwrap_or("(no branch)"),
+                message
+            );
+            let output =
+                GitOutput::ok(format!("Saved working directory and index state {}", entry));
+            state.stashes.insert(0, entry);
+            output
+        })
+    }
+
+    fn stashes(&self, repo: &Path) -> Vec<String> {
+        self.read("stashes", repo, |state| state.stashes.clone())
+            .unwrap_or_default()
+    }
+
+    fn stash_pop(&self, repo: &Path, index: usize) -> GitOutput {
+        self.mutate("stash_pop", repo, &[&index.to_string()], |state| {
+            if index >= state.stashes.len() {
+                return GitOutput::failed(format!(
+                    "error: stash@{{{}}} is not a valid reference",
+                    index
+                ));
+            }
+            state.stashes.remove(index);
+            GitOutput::ok(format!("Dropped refs/stash@{{{}}}", index))
+        })
+    }
+
+    fn tags(&self, repo: &Path) -> Vec<String> {
+        self.read("tags", repo, |state| state.tags.clone())
+            .unwrap_or_default()
+    }
+
+    /// Every known ref resolves to the latest commit; SHAs resolve to themselves.
+    fn resolve(&self, repo: &Path, rev: &str) -> Option<String> {
+        self.read("resolve", repo, |state| {
+            if let Some(commit) = state.commits.iter().find(|c| c.sha.starts_with(rev)) {
+                return Some(commit.sha.clone());
+            }
+            let known = rev == "HEAD"
+                || state.branches.iter().any(|b| b == rev)
+                || state.remote_branches.iter().any(|b| b == rev)
+                || state.tags.iter().any(|t| t == rev);
+            if !known {
+                return None;
+            }
+            state.commits.first().map(|c| c.sha.clone())
+        })?
+    }
+
+    fn create_tag(
+        &self,
+        repo: &Path,
+        tag: &str,
+        target: &str,
+        _message: &str,
+        _sign: bool,
+    ) -> GitOutput {
+        self.mutate("tag", repo, &[tag, target], |state| {
+            if state.tags.iter().any(|t| t == tag) {
+                return GitOutput::failed(format!("fatal: tag '{}' already exists", tag));
+            }
+            state.tags.push(tag.to_string());
+            GitOutput::ok("")
+        })
+    }
+
+    fn delete_tag(&self, repo: &Path, tag: &str) -> GitOutput {
+        self.mutate("tag-delete", repo, &[tag], |state| {
+            if !state.tags.iter().any(|t| t == tag) {
+                return GitOutput::failed(format!("error: tag '{}' not found.", tag));
+            }
+            state.tags.retain(|t| t != tag);
+            GitOutput::ok(format!("Deleted tag '{}'", tag))
+        })
+    }
+
+    fn push_tag(
+        &self,
+        repo: &Path,
+        remote: &str,
+        tag: &str,
+        delete: bool,
+        _options: &RunOptions,
+    ) -> GitOutput {
+        let action = if delete { "delete" } else { "push" };
+        self.mutate("push-tag", repo, &[remote, tag, action], |_| {
+            GitOutput::ok("")
+        })
+    }
+
+    fn clone_repo(&self, url: &str, path: &Path, _options: &RunOptions) -> GitOutput {
+        self.record("clone", path, &[url]);
+        let mut repos = self.repos.lock().unwrap();
+        if repos.contains_key(path) {
+            return GitOutput::failed(format!(
+                "fatal: destination path '{}' already exists",
+                path.display()
+            ));
+        }
+        repos.insert(
+            path.to_path_buf(),
+            FakeRepo::new("main").with_remote("origin", url),
+        );
+        GitOutput::ok("")
+    }
+
+    fn fetch(&self, repo: &Path, remote: &str, _options: &RunOptions) -> GitOutput {
+        self.mutate("fetch", repo, &[remote], |_| GitOutput::ok(""))
+    }
+
+    /// The remote is assumed to have every commit asked for.
+    fn fetch_commit(
+        &self,
+        repo: &Path,
+        remote: &str,
+        sha: &str,
+        _options: &RunOptions,
+    ) -> GitOutput {
+        self.mutate("fetch", repo, &[remote, sha], |state| {
+            if !state.remotes.iter().any(|r| r.name == remote) {
+                return GitOutput::failed(format!(
+                    "fatal: '{}' does not appear to be a git repository",
+                    remote
+                ));
+            }
+            if !state.commits.iter().any(|c| c.sha == sha) {
+                state.commits.push(Commit {
+                    sha: sha.to_string(),
+                    summary: String::new(),
+                });
+            }
+            GitOutput::ok("")
+        })
+    }
+
+    fn pull(&self, repo: &Path, remote: &str, branch: &str, _options: &RunOptions) -> GitOutput {
+        self.mutate("pull", repo, &[remote, branch], |state| {
+            match &mut state.upstream {
+                Some(upstream) if upstream.behind > 0 => {
+                    upstream.behind = 0;
+                    GitOutput::ok("Fast-forward")
+                }
+                _ => GitOutput::ok("Already up to date."),
+            }
+        })
+    }
+
+    fn push(&self, repo: &Path, remote: &str, branch: &str, _options: &RunOptions) -> GitOutput {
+        self.mutate("push", repo, &[remote, branch], |state| {
+            if let Some(upstream) = &mut state.upstream {
+                if upstream.behind > 0 {
+                    return GitOutput::failed(
+                        "! [rejected] (non-fast-forward)\nerror: failed to push some refs",
+                    );
+                }
+                upstream.ahead = 0;
+            }
+            GitOutput::ok("")
+        })
+    }
+
+    fn log(&self, repo: &Path, limit: usize) -> Vec<Commit> {
+        self.read("log", repo, |state| {
+            state.commits.iter().take(limit).cloned().collect()
+        })
+        .unwrap_or_default()
+    }
+}
+
+fn nothing_to_commit() -> GitOutput {
+    GitOutput {
+        success: false,
+        stdout: "nothing to commit, working tree clean".to_string(),
+        ..GitOutput::default()
+    }
+}
+
+fn add_commit(state: &mut FakeRepo, message: &str) -> GitOutput {
+    let summary = message.lines().next().unwrap_or_default();
+    state.commits.insert(
+        0,
+        Commit {
+            sha: format!("{:040x}", state.commits.len() + 1),
+            summary: summary.to_string(),
+        },
+    );
+    if let Some(upstream) = &mut state.upstream {
+        upstream.ahead += 1;
+    }
+    GitOutput::ok(format!("1 file changed\n{}", summary))
+}
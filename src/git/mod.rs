@@ -0,0 +1,467 @@
+mod cli;
+#[cfg(test)]
+pub mod fake;
+#[cfg(feature = "libgit2")]
+mod native;
+
+use colored::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::process::{self, Interrupt};
+
+pub use cli::CliGit;
+#[cfg(feature = "libgit2")]
+pub use native::NativeGit;
+
+/// Result of a git operation that changes a repository.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GitOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    /// Set when git was killed because of a timeout or Ctrl-C
+    pub interrupted: Option<Interrupt>,
+}
+
+impl GitOutput {
+    #[cfg(test)]
+    pub fn ok(stdout: impl Into<String>) -> Self {
+        Self {
+            success: true,
+            stdout: stdout.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn failed(stderr: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            stderr: stderr.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether git failed because authentication failed or was not possible
+    /// without prompting.
+    pub fn is_auth_failure(&self) -> bool {
+        !self.success
+            && self.interrupted.is_none()
+            && AUTH_FAILURES
+                .iter()
+                .any(|fragment| self.stderr.contains(fragment))
+    }
+
+    /// Whether git failed because of network trouble that may be transient.
+    /// Authentication failures, rejected pushes and timeouts are not retried.
+    pub fn is_retriable(&self) -> bool {
+        !self.success
+            && self.interrupted.is_none()
+            && !self.is_auth_failure()
+            && self.transient_error().is_some()
+    }
+
+    fn transient_error(&self) -> Option<&str> {
+        self.stderr.lines().find(|line| {
+            TRANSIENT_FAILURES
+                .iter()
+                .any(|fragment| line.contains(fragment))
+        })
+    }
+}
+
+/// Fragments of git and ssh error messages (in the C locale) that mean the
+/// remote rejected our credentials or would have needed to prompt for them.
+const AUTH_FAILURES: &[&str] = &[
+    "Permission denied (publickey",
+    "Permission denied, please try again",
+    "Host key verification failed",
+    "Authentication failed",
+    "could not read Username",
+    "could not read Password",
+    "terminal prompts disabled",
+    "Invalid username or password",
+    "HTTP Basic: Access denied",
+    "The requested URL returned error: 401",
+    "The requested URL returned error: 403",
+];
+
+/// Fragments of git and ssh error messages (in the C locale) for network
+/// trouble that is likely to go away if the operation is tried again.
+const TRANSIENT_FAILURES: &[&str] = &[
+    "Could not resolve host",
+    "Temporary failure in name resolution",
+    "Connection timed out",
+    "Operation timed out",
+    "Connection refused",
+    "Connection reset",
+    "Connection closed by",
+    "Network is unreachable",
+    "Failed to connect to",
+    "ssh: connect to host",
+    "kex_exchange_identification",
+    "The remote end hung up unexpectedly",
+    "early EOF",
+    "RPC failed",
+    "gnutls_handshake() failed",
+    "SSL_read",
+    "The requested URL returned error: 500",
+    "The requested URL returned error: 502",
+    "The requested URL returned error: 503",
+    "The requested URL returned error: 504",
+];
+
+/// Delay before the first retry; doubled for every retry after that.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Receives git's progress messages (e.g. `Receiving objects:  45% (9/20)`)
+/// and retry notices while an operation runs.
+pub type ProgressHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Time limits for operations that only touch the local repository, which
+/// take no `RunOptions`: a commit or a signed tag can still hang, e.g. on a
+/// lock or a passphrase prompt.
+#[derive(Debug, Clone, Default)]
+pub struct LocalTimeouts {
+    /// For repositories not listed in `repositories`
+    pub default: Option<Duration>,
+    /// By repository path
+    pub repositories: HashMap<PathBuf, Option<Duration>>,
+}
+
+impl LocalTimeouts {
+    pub fn for_repo(&self, repo: &Path) -> Option<Duration> {
+        self.repositories.get(repo).copied().unwrap_or(self.default)
+    }
+}
+
+/// Settings for git operations that talk to a remote.
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    /// Stop git if it has not finished after this long
+    pub timeout: Option<Duration>,
+    /// Value for `GIT_SSH_COMMAND`; by default ssh runs in batch mode so it
+    /// fails instead of prompting, and an empty string leaves it to git
+    pub ssh_command: Option<String>,
+    /// How often to retry transient network failures, see `with_retries`
+    pub retries: u32,
+    /// When set, git reports progress here instead of the operation printing it
+    pub progress: Option<ProgressHandler>,
+}
+
+/// One line of `git status --porcelain`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusEntry {
+    /// Two-letter porcelain code, e.g. ` M`, `A `, `??`
+    pub code: String,
+    pub path: String,
+}
+
+/// Commit counts relative to the upstream branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AheadBehind {
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub sha: String,
+    pub summary: String,
+}
+
+/// Everything gitpower needs from git. Commands only talk to repositories
+/// through this trait, so they can run against the real `git` binary or an
+/// in-memory fake.
+pub trait GitBackend: Send + Sync {
+    fn init(&self, repo: &Path) -> GitOutput;
+
+    /// Name of the checked-out branch, `None` when HEAD is detached.
+    fn current_branch(&self, repo: &Path) -> Option<String>;
+
+    fn status(&self, repo: &Path) -> Result<Vec<StatusEntry>, String>;
+
+    /// `None` when the current branch has no upstream.
+    fn ahead_behind(&self, repo: &Path) -> Option<AheadBehind>;
+
+    fn remotes(&self, repo: &Path) -> Vec<Remote>;
+
+    /// Names of the local branches.
+    fn branches(&self, repo: &Path) -> Vec<String>;
+
+    /// Remote-tracking branches as `<remote>/<branch>`, without `<remote>/HEAD`.
+    fn remote_branches(&self, repo: &Path) -> Vec<String>;
+
+    fn add_remote(&self, repo: &Path, name: &str, url: &str) -> GitOutput;
+
+    fn add_all(&self, repo: &Path) -> GitOutput;
+
+    fn commit(&self, repo: &Path, message: &str) -> GitOutput;
+
+    /// Commits all changes to tracked files, staged or not (`git commit -a`).
+    fn commit_tracked(&self, repo: &Path, message: &str) -> GitOutput;
+
+    /// Switches to a branch, or detaches HEAD when given a commit.
+    fn checkout(&self, repo: &Path, branch: &str) -> GitOutput;
+
+    /// Creates `branch` at `start` and checks it out. With `track`, `start`
+    /// (a remote-tracking branch) becomes its upstream.
+    fn create_branch(&self, repo: &Path, branch: &str, start: &str, track: bool) -> GitOutput;
+
+    /// Local branches whose tip is reachable from `target`.
+    fn merged_branches(&self, repo: &Path, target: &str) -> Vec<String>;
+
+    /// Local branches whose upstream no longer exists on the remote.
+    fn gone_branches(&self, repo: &Path) -> Vec<String>;
+
+    /// Commits on `branch` that no remote-tracking branch contains and, when
+    /// given, `base` does not either, newest first.
+    fn unpushed_commits(&self, repo: &Path, branch: &str, base: Option<&str>) -> Vec<Commit>;
+
+    /// Deletes a local branch. Without `force`, git refuses unmerged branches.
+    fn delete_branch(&self, repo: &Path, branch: &str, force: bool) -> GitOutput;
+
+    /// Stashes all local changes, including untracked files.
+    fn stash(&self, repo: &Path, message: &str) -> GitOutput;
+
+    /// Stash entries as `On <branch>: <message>`, newest (`stash@{0}`) first.
+    fn stashes(&self, repo: &Path) -> Vec<String>;
+
+    /// Applies and drops `stash@{index}`.
+    fn stash_pop(&self, repo: &Path, index: usize) -> GitOutput;
+
+    /// Names of the tags.
+    fn tags(&self, repo: &Path) -> Vec<String>;
+
+    /// SHA of the commit `rev` points to, `None` when it does not resolve.
+    fn resolve(&self, repo: &Path, rev: &str) -> Option<String>;
+
+    /// Creates an annotated tag, or a signed one with `sign`, at `target`.
+    fn create_tag(
+        &self,
+        repo: &Path,
+        tag: &str,
+        target: &str,
+        message: &str,
+        sign: bool,
+    ) -> GitOutput;
+
+    fn delete_tag(&self, repo: &Path, tag: &str) -> GitOutput;
+
+    /// Pushes `tag` to `remote`, or deletes it there with `delete`.
+    fn push_tag(
+        &self,
+        repo: &Path,
+        remote: &str,
+        tag: &str,
+        delete: bool,
+        options: &RunOptions,
+    ) -> GitOutput;
+
+    /// Clones `url` into `path`, which must not exist yet.
+    fn clone_repo(&self, url: &str, path: &Path, options: &RunOptions) -> GitOutput;
+
+    fn fetch(&self, repo: &Path, remote: &str, options: &RunOptions) -> GitOutput;
+
+    /// Fetches a single commit by SHA from the remote named `remote`.
+    fn fetch_commit(&self, repo: &Path, remote: &str, sha: &str, options: &RunOptions)
+    -> GitOutput;
+
+    fn pull(&self, repo: &Path, remote: &str, branch: &str, options: &RunOptions) -> GitOutput;
+
+    fn push(&self, repo: &Path, remote: &str, branch: &str, options: &RunOptions) -> GitOutput;
+
+    /// Most recent commits on HEAD, newest first.
+    fn log(&self, repo: &Path, limit: usize) -> Vec<Commit>;
+}
+
+/// Picks the backend named on the command line or in `GITPOWER_GIT_BACKEND`.
+pub fn backend(name: &str, timeouts: LocalTimeouts) -> Result<Box<dyn GitBackend>, String> {
+    match name {
+        "cli" => Ok(Box::new(CliGit::with_timeouts(timeouts))),
+        #[cfg(feature = "libgit2")]
+        "libgit2" => Ok(Box::new(NativeGit::with_timeouts(timeouts))),
+        #[cfg(not(feature = "libgit2"))]
+        "libgit2" => Err(
+            "gitpower was built without the `libgit2` feature; rebuild with `--features libgit2`"
+                .to_string(),
+        ),
+        other => Err(format!(
+            "Unknown git backend '{}'; expected 'cli' or 'libgit2'",
+            other
+        )),
+    }
+}
+
+/// Runs a network operation, retrying transient failures up to
+/// `options.retries` times with exponential backoff. Every failed attempt that
+/// is retried is reported; the caller prints the final output.
+pub fn with_retries(options: &RunOptions, operation: impl FnMut() -> GitOutput) -> GitOutput {
+    with_retries_reporting(options, &|line| println!("  {}", line), operation)
+}
+
+/// Like `with_retries`, handing the retry notices to `report` unless there is
+/// a progress display to show them on.
+pub fn with_retries_reporting(
+    options: &RunOptions,
+    report: &dyn Fn(&str),
+    mut operation: impl FnMut() -> GitOutput,
+) -> GitOutput {
+    let attempts = options.retries + 1;
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        let output = operation();
+        if attempt >= attempts || !output.is_retriable() || process::is_cancelled() {
+            return output;
+        }
+
+        let error = output.transient_error().unwrap_or_default().trim();
+        match &options.progress {
+            Some(progress) => progress(&format!(
+                "attempt {}/{} failed: {}; retrying in {}s",
+                attempt,
+                attempts,
+                error,
+                delay.as_secs()
+            )),
+            None => report(&format!(
+                "{} Attempt {}/{} failed: {}; retrying in {}s...",
+                "RETRY:".yellow().bold(),
+                attempt,
+                attempts,
+                error,
+                delay.as_secs()
+            )),
+        }
+        process::sleep(delay);
+
+        delay = (delay * 2).min(RETRY_MAX_DELAY);
+        attempt += 1;
+    }
+}
+
+/// Prints the output of a git operation indented under the repository header
+/// and returns whether it succeeded.
+pub fn print_output(output: &GitOutput) -> bool {
+    let stdout = output.stdout.as_str();
+    let stderr = output.stderr.as_str();
+
+    if !stdout.trim().is_empty() {
+        // Better formatting for command output
+        if stdout.lines().count() > 1 {
+            println!("  Output:");
+            for line in stdout.lines() {
+                println!("    {}", line);
+            }
+        } else {
+            println!("  Output: {}", stdout.trim());
+        }
+    }
+
+    if !stderr.trim().is_empty() {
+        // Better formatting for error output
+        if stderr.lines().count() > 1 {
+            eprintln!("  Errors:");
+            for line in stderr.lines() {
+                eprintln!("    {}", line.red());
+            }
+        } else {
+            eprintln!("  Error: {}", stderr.trim().red());
+        }
+    }
+
+    for hint in hints(output) {
+        println!("  {}", hint);
+    }
+
+    output.success
+}
+
+/// Explanations printed after a failed operation's output: why it failed
+/// when that is not obvious from git's own messages.
+pub fn hints(output: &GitOutput) -> Vec<String> {
+    let mut hints = Vec::new();
+    if output.is_auth_failure() {
+        hints.push(format!(
+            "{} Authentication failed; gitpower does not prompt for credentials, set up an SSH agent or credential helper",
+            "AUTH:".red().bold()
+        ));
+    }
+    match output.interrupted {
+        Some(Interrupt::TimedOut(limit)) => hints.push(format!(
+            "{} Timed out after {}s",
+            "TIMEOUT:".magenta().bold(),
+            limit.as_secs()
+        )),
+        Some(Interrupt::Cancelled) => {
+            hints.push(format!("{} Cancelled", "CANCELLED:".yellow().bold()))
+        }
+        None => {}
+    }
+    hints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn only_network_trouble_is_retried() {
+        let transient =
+            GitOutput::failed("ssh: connect to host example.com port 22: Connection refused");
+        assert!(transient.is_retriable());
+        let auth = GitOutput::failed(
+            "git@example.com: Permission denied (publickey).\nfatal: The remote end hung up unexpectedly",
+        );
+        assert!(auth.is_auth_failure());
+        assert!(!auth.is_retriable());
+        assert!(!GitOutput::failed("! [rejected] main -> main (non-fast-forward)").is_retriable());
+        assert!(!GitOutput::ok("").is_retriable());
+    }
+
+    #[test]
+    fn retries_transient_failures_and_reports_each_attempt() {
+        let options = RunOptions {
+            retries: 2,
+            ..RunOptions::default()
+        };
+        let reported = RefCell::new(Vec::new());
+        let mut attempts = 0;
+
+        let output = with_retries_reporting(
+            &options,
+            &|line| reported.borrow_mut().push(line.to_string()),
+            || {
+                attempts += 1;
+                match attempts {
+                    1 => GitOutput::failed(
+                        "fatal: unable to access: Could not resolve host: example.com",
+                    ),
+                    _ => GitOutput::ok("Already up to date."),
+                }
+            },
+        );
+
+        assert!(output.success);
+        assert_eq!(attempts, 2);
+        let reported = reported.into_inner();
+        assert_eq!(reported.len(), 1);
+        assert!(
+            reported[0].contains("Attempt 1/3 failed: fatal: unable to access"),
+            "{}",
+            reported[0]
+        );
+    }
+}
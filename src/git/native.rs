@@ -0,0 +1,313 @@
+use git2::{BranchType, Repository, Status, StatusOptions};
+use std::path::Path;
+
+use super::{
+    AheadBehind, CliGit, Commit, GitBackend, GitOutput, LocalTimeouts, Remote, RunOptions,
+    StatusEntry,
+};
+
+/// Backend that answers read-only queries in-process through libgit2 and
+/// hands everything that modifies a repository or talks to a remote to the
+/// `git` binary, so credentials, hooks and config behave exactly as with git.
+#[derive(Debug, Default, Clone)]
+pub struct NativeGit {
+    cli: CliGit,
+}
+
+impl NativeGit {
+    /// Backend whose operations handed to `git` are stopped after `timeouts`.
+    pub fn with_timeouts(timeouts: LocalTimeouts) -> Self {
+        Self {
+            cli: CliGit::with_timeouts(timeouts),
+        }
+    }
+}
+
+impl GitBackend for NativeGit {
+    fn init(&self, repo: &Path) -> GitOutput {
+        self.cli.init(repo)
+    }
+
+    fn current_branch(&self, repo: &Path) -> Option<String> {
+        let repo = Repository::open(repo).ok()?;
+        let head = repo.head().ok()?;
+        if !head.is_branch() {
+            return None;
+        }
+        head.shorthand().map(|name| name.to_string())
+    }
+
+    fn status(&self, repo: &Path) -> Result<Vec<StatusEntry>, String> {
+        let repo = Repository::open(repo).map_err(|e| e.message().to_string())?;
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(false)
+            .renames_head_to_index(true);
+
+        let statuses = repo
+            .statuses(Some(&mut options))
+            .map_err(|e| e.message().to_string())?;
+
+        Ok(statuses
+            .iter()
+            .filter(|entry| !entry.status().is_ignored())
+            .filter_map(|entry| {
+                Some(StatusEntry {
+                    code: porcelain_code(entry.status()),
+                    path: entry.path()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    fn ahead_behind(&self, repo: &Path) -> Option<AheadBehind> {
+        let repo = Repository::open(repo).ok()?;
+        let head = repo.head().ok()?;
+        let branch = repo
+            .find_branch(head.shorthand()?, BranchType::Local)
+            .ok()?;
+        let upstream = branch.upstream().ok()?;
+
+        let (ahead, behind) = repo
+            .graph_ahead_behind(head.target()?, upstream.get().target()?)
+            .ok()?;
+        Some(AheadBehind { ahead, behind })
+    }
+
+    fn remotes(&self, repo: &Path) -> Vec<Remote> {
+        let Ok(repo) = Repository::open(repo) else {
+            return Vec::new();
+        };
+        let Ok(names) = repo.remotes() else {
+            return Vec::new();
+        };
+
+        names
+            .iter()
+            .flatten()
+            .filter_map(|name| {
+                let remote = repo.find_remote(name).ok()?;
+                Some(Remote {
+                    name: name.to_string(),
+                    url: remote.url()?.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    fn branches(&self, repo: &Path) -> Vec<String> {
+        branch_names(repo, BranchType::Local)
+    }
+
+    fn remote_branches(&self, repo: &Path) -> Vec<String> {
+        branch_names(repo, BranchType::Remote)
+            .into_iter()
+            .filter(|name| !name.ends_with("/HEAD"))
+            .collect()
+    }
+
+    fn add_remote(&self, repo: &Path, name: &str, url: &str) -> GitOutput {
+        self.cli.add_remote(repo, name, url)
+    }
+
+    fn add_all(&self, repo: &Path) -> GitOutput {
+        self.cli.add_all(repo)
+    }
+
+    fn commit(&self, repo: &Path, message: &str) -> GitOutput {
+        self.cli.commit(repo, message)
+    }
+
+    fn commit_tracked(&self, repo: &Path, message: &str) -> GitOutput {
+        self.cli.commit_tracked(repo, message)
+    }
+
+    fn checkout(&self, repo: &Path, branch: &str) -> GitOutput {
+        self.cli.checkout(repo, branch)
+    }
+
+    fn create_branch(&self, repo: &Path, branch: &str, start: &str, track: bool) -> GitOutput {
+        self.cli.create_branch(repo, branch, start, track)
+    }
+
+    fn merged_branches(&self, repo: &Path, target: &str) -> Vec<String> {
+        self.cli.merged_branches(repo, target)
+    }
+
+    fn unpushed_commits(&self, repo: &Path, branch: &str, base: Option<&str>) -> Vec<Commit> {
+        self.cli.unpushed_commits(repo, branch, base)
+    }
+
+    fn gone_branches(&self, repo: &Path) -> Vec<String> {
+        self.cli.gone_branches(repo)
+    }
+
+    fn delete_branch(&self, repo: &Path, branch: &str, force: bool) -> GitOutput {
+        self.cli.delete_branch(repo, branch, force)
+    }
+
+    fn stash(&self, repo: &Path, message: &str) -> GitOutput {
+        self.cli.stash(repo, message)
+    }
+
+    fn stashes(&self, repo: &Path) -> Vec<String> {
+        self.cli.stashes(repo)
+    }
+
+    fn stash_pop(&self, repo: &Path, index: usize) -> GitOutput {
+        self.cli.stash_pop(repo, index)
+    }
+
+    fn tags(&self, repo: &Path) -> Vec<String> {
+        let Ok(repo) = Repository::open(repo) else {
+            return Vec::new();
+        };
+        let Ok(names) = repo.tag_names(None) else {
+            return Vec::new();
+        };
+        names.iter().flatten().map(str::to_string).collect()
+    }
+
+    fn resolve(&self, repo: &Path, rev: &str) -> Option<String> {
+        let repo = Repository::open(repo).ok()?;
+        let commit = repo.revparse_single(rev).ok()?.peel_to_commit().ok()?;
+        Some(commit.id().to_string())
+    }
+
+    fn create_tag(
+        &self,
+        repo: &Path,
+        tag: &str,
+        target: &str,
+        message: &str,
+        sign: bool,
+    ) -> GitOutput {
+        self.cli.create_tag(repo, tag, target, message, sign)
+    }
+
+    fn delete_tag(&self, repo: &Path, tag: &str) -> GitOutput {
+        self.cli.delete_tag(repo, tag)
+    }
+
+    fn push_tag(
+        &self,
+        repo: &Path,
+        remote: &str,
+        tag: &str,
+        delete: bool,
+        options: &RunOptions,
+    ) -> GitOutput {
+        self.cli.push_tag(repo, remote, tag, delete, options)
+    }
+
+    fn clone_repo(&self, url: &str, path: &Path, options: &RunOptions) -> GitOutput {
+        self.cli.clone_repo(url, path, options)
+    }
+
+    fn fetch(&self, repo: &Path, remote: &str, options: &RunOptions) -> GitOutput {
+        self.cli.fetch(repo, remote, options)
+    }
+
+    fn fetch_commit(
+        &self,
+        repo: &Path,
+        remote: &str,
+        sha: &str,
+        options: &RunOptions,
+    ) -> GitOutput {
+        self.cli.fetch_commit(repo, remote, sha, options)
+    }
+
+    fn pull(&self, repo: &Path, remote: &str, branch: &str, options: &RunOptions) -> GitOutput {
+        self.cli.pull(repo, remote, branch, options)
+    }
+
+    fn push(&self, repo: &Path, remote: &str, branch: &str, options: &RunOptions) -> GitOutput {
+        self.cli.push(repo, remote, branch, options)
+    }
+
+    fn log(&self, repo: &Path, limit: usize) -> Vec<Commit> {
+        let Ok(repo) = Repository::open(repo) else {
+            return Vec::new();
+        };
+        let Ok(mut walk) = repo.revwalk() else {
+            return Vec::new();
+        };
+        if walk.push_head().is_err() {
+            return Vec::new();
+        }
+
+        walk.flatten()
+            .take(limit)
+            .filter_map(|oid| {
+                let commit = repo.find_commit(oid).ok()?;
+                Some(Commit {
+                    sha: oid.to_string(),
+                    summary: commit.summary().unwrap_or_default().to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Names of the repository's local or remote-tracking branches.
+fn branch_names(repo: &Path, kind: BranchType) -> Vec<String> {
+    let Ok(repo) = Repository::open(repo) else {
+        return Vec::new();
+    };
+    let Ok(branches) = repo.branches(Some(kind)) else {
+        return Vec::new();
+    };
+
+    branches
+        .flatten()
+        .filter_map(|(branch, _)| Some(branch.name().ok()??.to_string()))
+        .collect()
+}
+
+/// Translates libgit2 status flags into the two-letter code of `git status --porcelain`.
+fn porcelain_code(status: Status) -> String {
+    if status.is_conflicted() {
+        return "UU".to_string();
+    }
+    if status.is_wt_new() && !status.intersects(index_flags()) {
+        return "??".to_string();
+    }
+
+    let index = if status.is_index_new() {
+        'A'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else if status.is_index_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+
+    let worktree = if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else if status.is_wt_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+
+    format!("{}{}", index, worktree)
+}
+
+fn index_flags() -> Status {
+    Status::INDEX_NEW
+        | Status::INDEX_MODIFIED
+        | Status::INDEX_DELETED
+        | Status::INDEX_RENAMED
+        | Status::INDEX_TYPECHANGE
+}
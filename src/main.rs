@@ -2,11 +2,16 @@ mod cli;
 mod commands;
 mod config;
 mod git;
+mod process;
+#[cfg(test)]
+mod testing;
 
+use clap::ArgMatches;
 use colored::Colorize;
-use config::Repository;
+use config::{Repository, Selection, TagFilter};
+use std::ffi::OsString;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 fn main() {
     let cli = cli::build_cli();
@@ -25,20 +30,85 @@ fn main() {
         return;
     }
 
-    let config_path = matches
-        .get_one::<String>("config")
-        .map(|s| s.as_str())
-        .unwrap_or("~/.config/gitpower/config.yml");
-    let config_path = shellexpand::tilde(config_path);
-    let config_path = Path::new(config_path.as_ref());
+    let config_path = match matches.get_one::<String>("config") {
+        Some(path) => shellexpand::tilde(path).into_owned(),
+        None => config::default_config_path(),
+    };
+    let config_path = Path::new(&config_path);
+
+    // Config maintenance commands work on the raw file, so handle them before loading it
+    if let Some(("config", sub_m)) = matches.subcommand() {
+        match sub_m.subcommand() {
+            Some(("migrate", _)) => commands::migrate_config(config_path),
+            Some(("schema", schema_m)) => commands::print_config_schema(
+                schema_m.get_one::<String>("output").map(|s| s.as_str()),
+            ),
+            Some(("convert", convert_m)) => commands::convert_config(
+                config_path,
+                convert_m.get_one::<String>("to").unwrap(),
+                convert_m.get_one::<String>("output").map(|s| s.as_str()),
+                convert_m.get_flag("force"),
+            ),
+            _ => unreachable!(),
+        }
+        return;
+    }
+
+    // Unknown commands run plugins. Look for the plugin first, since loading
+    // the config creates a default one when it is missing
+    let plugin = match matches.subcommand() {
+        Some((name, _)) if cli.find_subcommand(name).is_none() => {
+            match commands::find_plugin(name) {
+                Ok(program) => Some(program),
+                Err(e) => {
+                    eprintln!("{} {}", "ERROR:".red().bold(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
 
     let mut config = match config::load_config(config_path.to_str().unwrap()) {
         Ok(config) => config,
         Err(e) => {
             eprintln!("{}: {}", "Error with config".red(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let backend_name = matches
+        .get_one::<String>("git-backend")
+        .cloned()
+        .or_else(|| std::env::var("GITPOWER_GIT_BACKEND").ok())
+        .unwrap_or_else(|| "cli".to_string());
+    let timeouts = git::LocalTimeouts {
+        default: config.default_timeout(),
+        repositories: config
+            .repositories
+            .iter()
+            .map(|repo| {
+                let path = shellexpand::tilde(&repo.path);
+                (PathBuf::from(path.as_ref()), config.timeout_for(repo))
+            })
+            .collect(),
+    };
+    let git = match git::backend(&backend_name, timeouts) {
+        Ok(git) => git,
+        Err(e) => {
+            eprintln!("{} {}", "ERROR:".red().bold(), e);
             return;
         }
     };
+    let git = git.as_ref();
+
+    // `init` is driven by prompts, which Ctrl-C should simply abort
+    if matches.subcommand_name() != Some("init") {
+        process::install_interrupt_handler();
+    }
+
+    // Snapshot of the config as loaded, so saving can merge with concurrent edits
+    let base_config = config.clone();
 
     // Process commands
     match matches.subcommand() {
@@ -72,21 +142,22 @@ fn main() {
                 let expanded_path = shellexpand::tilde(path);
                 let repo_path = Path::new(expanded_path.as_ref());
 
-                if !repo_path.exists() {
-                    if let Err(e) = fs::create_dir_all(repo_path) {
-                        eprintln!(
-                            "{} Failed to create repository directory '{}': {}",
-                            "ERROR:".red(),
-                            repo_path.display(),
-                            e
-                        );
-                        return;
-                    }
+                if !repo_path.exists()
+                    && let Err(e) = fs::create_dir_all(repo_path)
+                {
+                    eprintln!(
+                        "{} Failed to create repository directory '{}': {}",
+                        "ERROR:".red(),
+                        repo_path.display(),
+                        e
+                    );
+                    return;
                 }
 
                 let remote = sub_m.get_one::<String>("remote").map(|s| s.as_str());
                 let branch = sub_m.get_one::<String>("branch").map(|s| s.as_str());
                 let groups = sub_m.get_one::<String>("groups").map(|s| s.as_str());
+                let tags = sub_m.get_one::<String>("tags").map(|s| s.as_str());
 
                 // Create repository and validate it
                 let repo = Repository {
@@ -95,6 +166,10 @@ fn main() {
                     remote: remote.map(|s| s.to_string()),
                     branch: branch.map(|s| s.to_string()),
                     groups: groups.map(|s| s.split(',').map(|s| s.trim().to_string()).collect()),
+                    tags: tags.map(|s| s.split(',').map(|s| s.trim().to_string()).collect()),
+                    timeout: None,
+                    ssh_command: None,
+                    hooks: None,
                 };
 
                 if let Err(e) = repo.validate() {
@@ -102,61 +177,291 @@ fn main() {
                     return;
                 }
 
-                commands::init_repository(&mut config, name, path, remote, branch, groups);
+                commands::init_repository(
+                    &mut config,
+                    git,
+                    name,
+                    path,
+                    remote,
+                    branch,
+                    groups,
+                    tags,
+                );
             } else {
                 // Handle interactive mode
-                commands::init_repository_interactive(&mut config);
+                commands::init_repository_interactive(&mut config, git);
             }
 
             // Save updated config
-            if let Ok(yaml) = serde_yaml::to_string(&config) {
-                if let Err(e) = fs::write(config_path, yaml) {
-                    eprintln!("{} Failed to save config: {}", "ERROR:".red(), e);
-                }
+            if let Err(e) = config::save_config(&base_config, &config, config_path) {
+                eprintln!("{} Failed to save config: {}", "ERROR:".red(), e);
             }
         }
-        Some(("list", _)) => commands::list_repositories(&config),
+        Some(("list", sub_m)) => commands::list_repositories(&config, &selection_from(sub_m)),
         Some(("status", sub_m)) => {
-            let repo_names: Vec<&str> = if let Some(values) = sub_m.get_many::<String>("repos") {
-                values.map(|s| s.as_str()).collect()
-            } else {
-                vec![] // Empty means all repositories
-            };
-            commands::show_repository_status(&config, &repo_names);
+            commands::show_repository_status(&config, &selection_from(sub_m), git);
         }
         Some(("sync", sub_m)) => {
-            let repo_names: Vec<&str> = if let Some(values) = sub_m.get_many::<String>("repos") {
-                values.map(|s| s.as_str()).collect()
-            } else {
-                vec![] // Empty means all repositories
-            };
-            commands::sync_repositories(&config, &repo_names);
+            commands::sync_repositories(
+                &config,
+                &selection_from(sub_m),
+                git,
+                sub_m.get_one::<u32>("retries").copied(),
+            );
         }
         Some(("pull", sub_m)) => {
-            let repo_names: Vec<&str> = if let Some(values) = sub_m.get_many::<String>("repos") {
-                values.map(|s| s.as_str()).collect()
-            } else {
-                vec![] // Empty means all repositories
-            };
-            commands::pull_repositories(&config, &repo_names);
+            commands::pull_repositories(
+                &config,
+                &selection_from(sub_m),
+                git,
+                sub_m.get_one::<u32>("retries").copied(),
+                jobs_from(sub_m),
+            );
         }
+        Some(("clone", sub_m)) => {
+            commands::clone_repositories(
+                &config,
+                &selection_from(sub_m),
+                git,
+                sub_m.get_one::<u32>("retries").copied(),
+                jobs_from(sub_m),
+            );
+        }
+        Some(("fetch", sub_m)) => {
+            commands::fetch_repositories(
+                &config,
+                &selection_from(sub_m),
+                git,
+                sub_m.get_one::<u32>("retries").copied(),
+                jobs_from(sub_m),
+            );
+        }
+        Some(("branch", sub_m)) => match sub_m.subcommand() {
+            Some(("checkout", branch_m)) => commands::checkout_branch(
+                &config,
+                &selection_from(branch_m),
+                git,
+                branch_m.get_one::<String>("name").unwrap(),
+                branch_m.get_flag("stash"),
+            ),
+            Some(("create", branch_m)) => commands::create_branch(
+                &config,
+                &selection_from(branch_m),
+                git,
+                branch_m.get_one::<String>("name").unwrap(),
+                branch_m.get_one::<String>("from").map(String::as_str),
+                branch_m.get_flag("stash"),
+            ),
+            Some(("list", branch_m)) => commands::list_branches(
+                &config,
+                &selection_from(branch_m),
+                git,
+                branch_m.get_flag("all"),
+            ),
+            Some(("prune", branch_m)) => commands::prune_branches(
+                &config,
+                &selection_from(branch_m),
+                git,
+                branch_m.get_flag("yes"),
+                branch_m.get_flag("force"),
+            ),
+            _ => unreachable!(),
+        },
+        Some(("commit", sub_m)) => commands::commit_repositories(
+            &config,
+            &selection_from(sub_m),
+            git,
+            sub_m.get_one::<String>("message").unwrap(),
+            &commands::CommitSettings {
+                all: sub_m.get_flag("all"),
+                trailers: sub_m
+                    .get_many::<String>("trailer")
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect(),
+                change_set: sub_m.get_flag("change-set"),
+            },
+        ),
+        Some(("tag", sub_m)) => match sub_m.subcommand() {
+            Some(("delete", tag_m)) => commands::delete_tag(
+                &config,
+                &selection_from(tag_m),
+                git,
+                tag_m.get_one::<String>("name").unwrap(),
+                tag_m.get_flag("remote"),
+                tag_m.get_one::<u32>("retries").copied(),
+            ),
+            Some(("list", tag_m)) => commands::list_tags(&config, &selection_from(tag_m), git),
+            _ => commands::create_tag(
+                &config,
+                &selection_from(sub_m),
+                git,
+                sub_m.get_one::<String>("name").unwrap(),
+                &commands::TagSettings {
+                    message: sub_m.get_one::<String>("message").cloned(),
+                    sign: sub_m.get_flag("sign"),
+                    target: sub_m.get_one::<String>("ref").cloned(),
+                    push: sub_m.get_flag("push"),
+                    retries: sub_m.get_one::<u32>("retries").copied(),
+                },
+            ),
+        },
+        Some(("snapshot", sub_m)) => match sub_m.subcommand() {
+            Some(("save", snapshot_m)) => commands::snapshot_save(
+                &config,
+                &selection_from(snapshot_m),
+                git,
+                Path::new(snapshot_m.get_one::<String>("file").unwrap()),
+            ),
+            Some(("restore", snapshot_m)) => commands::snapshot_restore(
+                &config,
+                &selection_from(snapshot_m),
+                git,
+                Path::new(snapshot_m.get_one::<String>("file").unwrap()),
+                snapshot_m.get_flag("detach"),
+                snapshot_m.get_flag("stash"),
+            ),
+            Some(("verify", snapshot_m)) => {
+                let matches = commands::snapshot_verify(
+                    &config,
+                    &selection_from(snapshot_m),
+                    git,
+                    Path::new(snapshot_m.get_one::<String>("file").unwrap()),
+                );
+                if !matches {
+                    std::process::exit(1);
+                }
+            }
+            _ => unreachable!(),
+        },
+        Some(("stash", sub_m)) => match sub_m.subcommand() {
+            Some(("save", stash_m)) => commands::stash_save(
+                &config,
+                &selection_from(stash_m),
+                git,
+                stash_m.get_one::<String>("message").unwrap(),
+            ),
+            Some(("list", stash_m)) => commands::stash_list(&config, &selection_from(stash_m), git),
+            Some(("pop", stash_m)) => commands::stash_pop(
+                &config,
+                &selection_from(stash_m),
+                git,
+                stash_m.get_one::<String>("batch").map(String::as_str),
+            ),
+            _ => unreachable!(),
+        },
         Some(("run", sub_m)) => {
-            let command = sub_m.get_one::<String>("command").unwrap();
-            let repo_names: Vec<&str> = if let Some(values) = sub_m.get_many::<String>("repos") {
-                values.map(|s| s.as_str()).collect()
-            } else {
-                vec![] // Empty means all repositories
+            let command = sub_m.get_one::<String>("command");
+            let shell = sub_m.get_one::<String>("shell").unwrap();
+            let mut selection = selection_from(sub_m);
+            let invocation = match sub_m.get_many::<String>("argv") {
+                Some(argv) => {
+                    // Everything before `--` names repositories or groups
+                    if let Some(target) = command {
+                        selection.names.insert(0, target.as_str());
+                    }
+                    commands::Invocation::Argv(argv.cloned().collect())
+                }
+                None => commands::Invocation::Shell {
+                    shell: shell.clone(),
+                    command: command.unwrap().clone(),
+                },
             };
-            commands::run_command(&config, command, &repo_names);
+            let settings = commands::RunSettings {
+                jobs: jobs_from(sub_m),
+                output: commands::OutputMode::from_name(sub_m.get_one::<String>("output").unwrap())
+                    .unwrap(),
+                dry_run: sub_m.get_flag("dry-run"),
+                only_if: sub_m.get_one::<String>("only-if").map(|condition| {
+                    commands::Invocation::Shell {
+                        shell: shell.clone(),
+                        command: condition.clone(),
+                    }
+                }),
+                fail_fast: sub_m.get_flag("fail-fast"),
+                max_failures: sub_m
+                    .get_one::<u32>("max-failures")
+                    .map(|limit| *limit as usize),
+                output_dir: sub_m
+                    .get_one::<String>("output-dir")
+                    .map(|dir| PathBuf::from(shellexpand::tilde(dir).as_ref())),
+                runs_dir: commands::runs_dir(config_path),
+            };
+            commands::run_command(&config, git, &invocation, &selection, &settings);
         }
-        Some(("interactive", _)) => {
-            let mut app = commands::App::new(config);
+        Some(("do", sub_m)) => match sub_m.get_one::<String>("name") {
+            Some(name) => {
+                let settings = commands::DoSettings {
+                    jobs: jobs_from(sub_m),
+                    output: commands::OutputMode::from_name(
+                        sub_m.get_one::<String>("output").unwrap(),
+                    )
+                    .unwrap(),
+                    retries: sub_m.get_one::<u32>("retries").copied(),
+                    dry_run: sub_m.get_flag("dry-run"),
+                    runs_dir: commands::runs_dir(config_path),
+                };
+                commands::run_named(&config, git, name, &selection_from(sub_m), &settings);
+            }
+            None => commands::list_named(&config),
+        },
+        Some(("runs", sub_m)) => match sub_m.subcommand() {
+            Some(("list", _)) => commands::list_runs(&commands::runs_dir(config_path)),
+            Some(("show", show_m)) => commands::show_run(
+                &commands::runs_dir(config_path),
+                show_m.get_one::<String>("id").unwrap(),
+                show_m.get_flag("logs"),
+            ),
+            _ => unreachable!(),
+        },
+        Some(("interactive", sub_m)) => {
+            let mut app = commands::App::new(config, &selection_from(sub_m), git);
             if let Err(e) = app.run() {
                 eprintln!("{}: {}", "Error in interactive mode".red(), e);
             }
         }
+        Some((name, sub_m)) => {
+            let args: Vec<OsString> = sub_m
+                .get_many::<OsString>("")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            let program = plugin.expect("plugins are looked up before loading the config");
+            std::process::exit(commands::run_plugin(
+                &config,
+                config_path,
+                &program,
+                name,
+                &args,
+            ));
+        }
         _ => {
             println!("No command specified. Try 'gitpower --help' for more information.");
         }
     }
 }
+
+/// Builds the repository selection from the `repos` positional and tag filter arguments.
+fn selection_from(matches: &ArgMatches) -> Selection<'_> {
+    // Not every command takes positional repos, so look arguments up fallibly
+    let values = |id: &str| -> Vec<&str> {
+        matches
+            .try_get_many::<String>(id)
+            .ok()
+            .flatten()
+            .map(|values| values.map(|s| s.as_str()).collect())
+            .unwrap_or_default()
+    };
+
+    Selection {
+        // Empty means all repositories
+        names: values("repos"),
+        tags: TagFilter::new(values("tag"), values("not-tag")),
+    }
+}
+
+fn jobs_from(matches: &ArgMatches) -> usize {
+    matches.get_one::<u32>("jobs").copied().unwrap_or(1) as usize
+}
@@ -0,0 +1,290 @@
+//! Spawning child processes that can be timed out or cancelled with Ctrl-C.
+//!
+//! Children run in their own process group, so a Ctrl-C on the terminal only
+//! reaches gitpower. The handler flags the run as cancelled and every running
+//! child, together with anything it spawned (e.g. `ssh`), is stopped from
+//! here: asked to terminate first, so git can remove its lock files, and
+//! killed if it is still running after a grace period.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use colored::*;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static PROMPTING: AtomicBool = AtomicBool::new(false);
+/// Set while a live display owns the terminal, see `hold_interrupt_notice`
+static NOTICE_HELD: AtomicBool = AtomicBool::new(false);
+static NOTICE_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// How often a running child is checked for exit, timeout and cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long a child gets to exit after SIGTERM before it is killed.
+const TERMINATE_GRACE: Duration = Duration::from_secs(2);
+
+/// Why a child was stopped before it exited on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    TimedOut(Duration),
+    Cancelled,
+}
+
+/// Captured result of a child process.
+#[derive(Debug)]
+pub struct Finished {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Set when the child was stopped because of a timeout or Ctrl-C
+    pub interrupted: Option<Interrupt>,
+}
+
+/// Installs the Ctrl-C handler. The first Ctrl-C stops running children and
+/// lets commands print what they got done; a second one exits immediately,
+/// as does one during a `prompt`.
+pub fn install_interrupt_handler() {
+    let result = ctrlc::set_handler(|| {
+        if PROMPTING.load(Ordering::SeqCst) {
+            // Prompts hide the cursor while waiting for a key
+            let _ = crossterm::execute!(io::stderr(), crossterm::cursor::Show);
+            eprintln!();
+            std::process::exit(130);
+        }
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            let _ = crossterm::execute!(io::stderr(), crossterm::cursor::Show);
+            std::process::exit(130);
+        }
+        if NOTICE_HELD.load(Ordering::SeqCst) {
+            NOTICE_PENDING.store(true, Ordering::SeqCst);
+        } else {
+            print_interrupt_notice();
+        }
+    });
+
+    if let Err(e) = result {
+        eprintln!(
+            "{}: Failed to install Ctrl-C handler: {}",
+            "Warning".yellow(),
+            e
+        );
+    }
+}
+
+fn print_interrupt_notice() {
+    eprintln!(
+        "\n{} Interrupted, stopping running commands (press Ctrl-C again to exit immediately)",
+        "WARNING:".yellow().bold()
+    );
+}
+
+/// Runs `draw`, a display redrawn in place, holding back the Ctrl-C notice
+/// until it returns so the notice does not land in the middle of it.
+pub fn hold_interrupt_notice<R>(draw: impl FnOnce() -> R) -> R {
+    NOTICE_HELD.store(true, Ordering::SeqCst);
+    let result = draw();
+    NOTICE_HELD.store(false, Ordering::SeqCst);
+    if NOTICE_PENDING.swap(false, Ordering::SeqCst) {
+        print_interrupt_notice();
+    }
+    result
+}
+
+/// Runs `ask`, an interactive prompt, with Ctrl-C exiting as it does without
+/// the handler. Nothing is running yet that could be stopped, and the prompt
+/// would otherwise carry on as if the key had not been pressed.
+pub fn prompt<T>(ask: impl FnOnce() -> T) -> T {
+    PROMPTING.store(true, Ordering::SeqCst);
+    let answer = ask();
+    PROMPTING.store(false, Ordering::SeqCst);
+    answer
+}
+
+/// Whether the user has pressed Ctrl-C during this run.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Sleeps for `duration`, returning early if the run is cancelled.
+pub fn sleep(duration: Duration) {
+    let started = Instant::now();
+    while !is_cancelled() && started.elapsed() < duration {
+        thread::sleep(POLL_INTERVAL.min(duration.saturating_sub(started.elapsed())));
+    }
+}
+
+/// Receives each line a child writes, as soon as it is written.
+pub type LineHandler<'a> = &'a (dyn Fn(Stream, &str) + Sync);
+
+/// Which pipe of the child a line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Runs `command` to completion with captured output, stopping it once
+/// `timeout` elapses or the run is cancelled.
+pub fn run(command: Command, timeout: Option<Duration>) -> io::Result<Finished> {
+    run_inner(command, timeout, None, b"\n")
+}
+
+/// Like `run`, but also hands every line to `on_line` as soon as the child
+/// writes it. Lines are passed without their terminator.
+pub fn run_streaming(
+    command: Command,
+    timeout: Option<Duration>,
+    on_line: LineHandler,
+) -> io::Result<Finished> {
+    run_inner(command, timeout, Some(on_line), b"\n")
+}
+
+/// Like `run_streaming`, but also splits on carriage returns, so every
+/// update of a progress meter redrawn in place (as git's `--progress` does)
+/// arrives separately.
+pub fn run_progress(
+    command: Command,
+    timeout: Option<Duration>,
+    on_update: LineHandler,
+) -> io::Result<Finished> {
+    run_inner(command, timeout, Some(on_update), b"\r\n")
+}
+
+fn run_inner(
+    mut command: Command,
+    timeout: Option<Duration>,
+    on_line: Option<LineHandler>,
+    separators: &[u8],
+) -> io::Result<Finished> {
+    if is_cancelled() {
+        return Ok(Finished {
+            status: ExitStatus::from_raw(libc::SIGINT),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            interrupted: Some(Interrupt::Cancelled),
+        });
+    }
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+
+    let stdout_pipe = child.stdout.take();
+    let stderr_pipe = child.stderr.take();
+
+    thread::scope(|scope| {
+        let stdout = scope.spawn(|| drain(stdout_pipe, Stream::Stdout, on_line, separators));
+        let stderr = scope.spawn(|| drain(stderr_pipe, Stream::Stderr, on_line, separators));
+
+        let (status, interrupted) = wait(&mut child, timeout)?;
+
+        Ok(Finished {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+            interrupted,
+        })
+    })
+}
+
+fn wait(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> io::Result<(ExitStatus, Option<Interrupt>)> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, None));
+        }
+
+        let interrupt = if is_cancelled() {
+            Some(Interrupt::Cancelled)
+        } else {
+            timeout
+                .filter(|limit| started.elapsed() >= *limit)
+                .map(Interrupt::TimedOut)
+        };
+
+        if let Some(interrupt) = interrupt {
+            return Ok((stop_group(child)?, Some(interrupt)));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Reads a pipe to the end so a chatty child never blocks on a full pipe
+/// while we are waiting for it, forwarding each piece of output ending in one
+/// of `separators` to `on_line`.
+fn drain(
+    pipe: Option<impl Read>,
+    stream: Stream,
+    on_line: Option<LineHandler>,
+    separators: &[u8],
+) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let Some(pipe) = pipe else {
+        return buffer;
+    };
+
+    let Some(on_line) = on_line else {
+        let _ = BufReader::new(pipe).read_to_end(&mut buffer);
+        return buffer;
+    };
+
+    let emit = |line: &[u8]| {
+        let line = String::from_utf8_lossy(line);
+        on_line(stream, line.trim_end_matches('\r'));
+    };
+
+    let mut reader = BufReader::new(pipe);
+    let mut line_start = 0;
+    loop {
+        let chunk = match reader.fill_buf() {
+            Ok([]) | Err(_) => break,
+            Ok(chunk) => chunk,
+        };
+        let len = chunk.len();
+        for &byte in chunk {
+            buffer.push(byte);
+            if separators.contains(&byte) {
+                emit(&buffer[line_start..buffer.len() - 1]);
+                line_start = buffer.len();
+            }
+        }
+        reader.consume(len);
+    }
+    if line_start < buffer.len() {
+        emit(&buffer[line_start..]);
+    }
+    buffer
+}
+
+/// Sends SIGTERM to the child's process group and waits for the child to
+/// exit, sending SIGKILL once `TERMINATE_GRACE` has passed.
+fn stop_group(child: &mut Child) -> io::Result<ExitStatus> {
+    signal_group(child, libc::SIGTERM);
+    let started = Instant::now();
+    while started.elapsed() < TERMINATE_GRACE {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    signal_group(child, libc::SIGKILL);
+    child.wait()
+}
+
+fn signal_group(child: &Child, signal: libc::c_int) {
+    // The child leads its own process group, see `process_group(0)` above
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), signal);
+    }
+}
@@ -0,0 +1,92 @@
+//! Fixtures shared by the tests.
+
+use crate::config::{Config, Repository};
+use crate::git::fake::{FakeGit, FakeRepo};
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// Repository `name` at `path`, with every optional setting left out.
+pub fn repository(name: &str, path: &Path) -> Repository {
+    Repository {
+        name: name.to_string(),
+        path: path.display().to_string(),
+        remote: None,
+        branch: None,
+        groups: None,
+        tags: None,
+        timeout: None,
+        ssh_command: None,
+        hooks: None,
+    }
+}
+
+/// Empty directory for `test`, removed again when dropped.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn new(test: &str) -> ScratchDir {
+        let dir = std::env::temp_dir().join(format!("gitpower-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        ScratchDir(dir)
+    }
+}
+
+impl Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Configured repositories backed by a `FakeGit`. Each gets a directory of
+/// its own, since commands skip repositories whose path is missing.
+pub struct Fixture {
+    pub config: Config,
+    pub git: FakeGit,
+    /// Repository paths, in the order the repositories were given
+    pub paths: Vec<PathBuf>,
+    pub dir: ScratchDir,
+}
+
+impl Fixture {
+    /// Repository `name` in `<dir>/<name>` for every `(name, repo)` pair.
+    pub fn new<'a>(test: &str, repos: impl IntoIterator<Item = (&'a str, FakeRepo)>) -> Fixture {
+        let dir = ScratchDir::new(test);
+        let mut git = FakeGit::new();
+        let mut repositories = Vec::new();
+        let mut paths = Vec::new();
+        for (name, repo) in repos {
+            let path = dir.join(name);
+            fs::create_dir_all(&path).unwrap();
+            git = git.with_repo(&path, repo);
+            repositories.push(repository(name, &path));
+            paths.push(path);
+        }
+
+        let config = Config {
+            version: 1,
+            defaults: None,
+            repositories,
+            groups: None,
+            commands: None,
+            workflows: None,
+            hooks: None,
+            plugins: None,
+        };
+        Fixture {
+            config,
+            git,
+            paths,
+            dir,
+        }
+    }
+}